@@ -0,0 +1,37 @@
+;; An unprompt plugin that shows the VERSION file of the current directory.
+;; Build it with `wat2wasm version.wat` and use it in a section with type = "wasm".
+(module
+  (import "unprompt" "read_file" (func $read_file (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "VERSION")
+  (data (i32.const 16) "{\"visible\":false}")
+  (data (i32.const 1015) "{\"text\":\"")
+
+  ;; the request goes after the plugin's own data, growing memory if it doesn't fit
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $needed i32)
+    (local.set $needed (i32.add (i32.const 4096) (local.get $len)))
+    (if (i32.gt_u (local.get $needed) (i32.mul (memory.size) (i32.const 65536)))
+      (then (drop (memory.grow (i32.add (i32.div_u (local.get $needed) (i32.const 65536)) (i32.const 1))))))
+    (i32.const 4096))
+
+  ;; answers with {"text":"<version>"}, or hides the section when there's no VERSION file
+  (func (export "collect") (param $ptr i32) (param $len i32) (result i64)
+    (local $n i32)
+    (local.set $n (call $read_file (i32.const 0) (i32.const 7) (i32.const 1024) (i32.const 64)))
+    (if (i32.lt_s (local.get $n) (i32.const 0))
+      (then (return (i64.or (i64.shl (i64.const 16) (i64.const 32)) (i64.const 17)))))
+    (if (i32.gt_u (local.get $n) (i32.const 64))
+      (then (local.set $n (i32.const 64))))
+    ;; drop the trailing newline and spaces
+    (block $trimmed
+      (loop $trim
+        (br_if $trimmed (i32.eqz (local.get $n)))
+        (br_if $trimmed (i32.gt_u (i32.load8_u (i32.add (i32.const 1023) (local.get $n))) (i32.const 32)))
+        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+        (br $trim)))
+    ;; closes the JSON with "}
+    (i32.store16 (i32.add (i32.const 1024) (local.get $n)) (i32.const 0x7d22))
+    (i64.or
+      (i64.shl (i64.const 1015) (i64.const 32))
+      (i64.extend_i32_u (i32.add (local.get $n) (i32.const 11))))))
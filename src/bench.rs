@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+use unprompt::Context;
+
+// every stage's time over all the runs, in the order stages were first seen
+#[derive(Default)]
+struct Samples {
+    stages: Vec<(String, Vec<Duration>)>,
+}
+
+impl Samples {
+    fn add(&mut self, stage: &str, time: Duration) {
+        match self.stages.iter_mut().find(|(name, _)| name == stage) {
+            Some((_, times)) => times.push(time),
+            None => self.stages.push((stage.to_string(), vec![time])),
+        }
+    }
+}
+
+// renders the config `runs` times, loading it again every time like the shell does
+pub fn bench(config: Option<&str>, context: &Context, runs: usize) {
+    let mut samples = Samples::default();
+    for _ in 0..runs {
+        let start = Instant::now();
+        let prompt = unprompt::parse_config(config);
+        samples.add("config load", start.elapsed());
+        let rendered = prompt.render(context);
+        for (section, segment, time) in &rendered.timings.collect {
+            samples.add(&format!("collect {section} ({segment})"), *time);
+        }
+        samples.add("layout", rendered.timings.layout);
+        samples.add("format", rendered.timings.format);
+        samples.add("total", start.elapsed());
+    }
+
+    println!(
+        "{} runs of {} at {} columns",
+        runs,
+        config.unwrap_or("default.toml"),
+        context.columns
+    );
+    let name_width = samples
+        .stages
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or_default();
+    println!(
+        "{:name_width$}  {:>10}  {:>10}  {:>10}",
+        "stage", "p50", "p95", "max"
+    );
+    for (name, times) in &mut samples.stages {
+        times.sort();
+        println!(
+            "{name:name_width$}  {:>10}  {:>10}  {:>10}",
+            millis(percentile(times, 50)),
+            millis(percentile(times, 95)),
+            millis(percentile(times, 100)),
+        );
+    }
+}
+
+// nearest rank on sorted times
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+fn millis(time: Duration) -> String {
+    format!("{:.3} ms", time.as_secs_f64() * 1000.0)
+}
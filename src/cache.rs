@@ -0,0 +1,52 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{Value, json};
+
+use crate::{context::Context, segment::SegmentData};
+
+fn cache_dir(context: &Context) -> PathBuf {
+    let cache_home = context
+        .var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(context.home()).join(".cache"));
+    cache_home.join("unprompt")
+}
+
+// one entry per section and directory, since most segments depend on where the shell is
+pub fn entry_path(section_name: &str, segment_name: &str, context: &Context) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    (section_name, segment_name, &context.cwd).hash(&mut hasher);
+    cache_dir(context).join(format!("{:016x}.json", hasher.finish()))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+// max_age of None accepts an entry of any age
+pub fn read(path: &Path, max_age: Option<Duration>) -> Option<SegmentData> {
+    let entry: Value = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    let written = entry.get("written")?.as_u64()?;
+    if max_age.is_some_and(|max_age| now().saturating_sub(written) > max_age.as_secs()) {
+        return None;
+    }
+    Some(SegmentData::from_json(entry.get("data")?))
+}
+
+// the cache only saves time, so failing to write it isn't an error
+pub fn write(path: &Path, data: &SegmentData) {
+    let entry = json!({ "written": now(), "data": data.to_json() });
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let _ = fs::write(path, entry.to_string());
+}
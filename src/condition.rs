@@ -0,0 +1,144 @@
+use std::str::FromStr;
+
+use crate::context::Context;
+
+// a [[sections.x.when]] rule, whose values replace the section's while its condition holds
+#[derive(Clone)]
+pub struct WhenRule {
+    pub condition: Condition,
+    pub foreground: Option<String>, // escape codes, resolved when the config is parsed
+    pub background: Option<String>,
+    pub icon: Option<String>,
+    pub format: Option<String>,
+}
+
+// clauses joined by "and", like `status != 0 and env.SSH_CONNECTION exists`
+#[derive(Clone)]
+pub struct Condition {
+    clauses: Vec<Clause>,
+}
+
+#[derive(Clone)]
+enum Clause {
+    Exists(Operand),
+    Missing(Operand),
+    Compare(Operand, Op, String),
+}
+
+#[derive(Clone)]
+enum Operand {
+    Status, // exit code of the last command
+    User,
+    Host,
+    Text, // the section's text, after its segment filled it in
+    Env(String),
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl FromStr for Condition {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let clauses = s
+            .split(" and ")
+            .map(parse_clause)
+            .collect::<Result<Vec<Clause>, String>>()?;
+        Ok(Condition { clauses })
+    }
+}
+
+fn parse_clause(clause: &str) -> Result<Clause, String> {
+    let clause = clause.trim();
+    let (operand, rest) = clause
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| format!("'{clause}' needs an operator"))?;
+    let operand = match operand {
+        "status" => Operand::Status,
+        "user" => Operand::User,
+        "host" => Operand::Host,
+        "text" => Operand::Text,
+        _ => match operand.strip_prefix("env.") {
+            Some(var) if !var.is_empty() => Operand::Env(var.to_string()),
+            _ => return Err(format!("unknown value '{operand}'")),
+        },
+    };
+    let rest = rest.trim();
+    match rest {
+        "exists" => return Ok(Clause::Exists(operand)),
+        "missing" => return Ok(Clause::Missing(operand)),
+        _ => (),
+    }
+    // two character operators go first, so '<=' isn't read as '<'
+    let ops = [
+        ("==", Op::Eq),
+        ("!=", Op::Ne),
+        ("<=", Op::Le),
+        (">=", Op::Ge),
+        ("<", Op::Lt),
+        (">", Op::Gt),
+    ];
+    let (op, value) = ops
+        .iter()
+        .find_map(|(symbol, op)| rest.strip_prefix(symbol).map(|value| (*op, value.trim())))
+        .ok_or_else(|| format!("unknown operator in '{clause}'"))?;
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+    Ok(Clause::Compare(operand, op, value.to_string()))
+}
+
+impl Condition {
+    pub fn matches(&self, text: &str, context: &Context) -> bool {
+        self.clauses.iter().all(|clause| match clause {
+            Clause::Exists(operand) => operand.value(text, context).is_some(),
+            Clause::Missing(operand) => operand.value(text, context).is_none(),
+            // a value that isn't there never matches, so `status != 0` needs a status
+            Clause::Compare(operand, op, expected) => operand
+                .value(text, context)
+                .is_some_and(|value| op.compare(&value, expected)),
+        })
+    }
+}
+
+impl Operand {
+    fn value(&self, text: &str, context: &Context) -> Option<String> {
+        match self {
+            Operand::Status => context.status.map(|status| status.to_string()),
+            Operand::User => context.var("USER").map(str::to_string),
+            Operand::Host => Some(context.hostname.clone()).filter(|host| !host.is_empty()),
+            Operand::Text => Some(text.to_string()),
+            Operand::Env(var) => context.var(var).map(str::to_string),
+        }
+    }
+}
+
+impl Op {
+    // numbers compare as numbers, anything else only by equality
+    fn compare(self, value: &str, expected: &str) -> bool {
+        match self {
+            Op::Eq => value == expected,
+            Op::Ne => value != expected,
+            _ => {
+                let (Ok(value), Ok(expected)) = (value.parse::<i64>(), expected.parse::<i64>())
+                else {
+                    return false;
+                };
+                match self {
+                    Op::Lt => value < expected,
+                    Op::Le => value <= expected,
+                    Op::Gt => value > expected,
+                    _ => value >= expected,
+                }
+            }
+        }
+    }
+}
@@ -1,167 +1,505 @@
-use std::{env, fs::File, io::Read, str::FromStr};
+use std::{fs::File, io::Read, str::FromStr};
 
-use prompt::{Position, Prompt};
-use toml::{Value, map::Map};
+use regex::Regex;
+use toml::Value;
 
-#[path = "./prompt.rs"]
-pub mod prompt;
+use crate::{
+    condition::WhenRule,
+    path,
+    prompt::{self, Position, Prompt, PromptLine},
+    segment, shell,
+};
 
-pub fn parse_config(config_path: Option<&str>) -> Prompt {
-    let config_table = match config_path {
+// keys every section understands, segments can add their own
+pub(crate) const SECTION_KEYS: &[&str] = &[
+    "type",
+    "text",
+    "path",
+    "icon",
+    "readonly_icon",
+    "missing_icon",
+    "symlink_icon",
+    "format",
+    "parent_format",
+    "separator_format",
+    "basename_format",
+    "short_format",
+    "min_format",
+    "visible",
+    "priority",
+    "foreground",
+    "background",
+    "position",
+    "order",
+    "options",
+    "shorten",
+    "root_markers",
+    "substitutions",
+    "timeout",
+    "cache",
+    "when",
+];
+
+pub fn parse_config(config_path: Option<&str>) -> Prompt<'static> {
+    let contents = match config_path {
         Some(path) => read_config(path),
         None => read_config("default.toml"),
     };
-    let mut prompt: Prompt = Default::default();
+    parse_config_str(&contents)
+}
+
+pub fn parse_config_str(contents: &str) -> Prompt<'static> {
+    let config_table = contents.parse::<toml::Table>().unwrap();
+    let mut prompt: Prompt = Prompt {
+        segments: segment::builtin_segments(),
+        ..Default::default()
+    };
     let prompt_config = config_table.get_key_value("prompt").unwrap().1;
     let sections_config = config_table.get_key_value("sections").unwrap().1;
 
     parse_prompt_config(&mut prompt, prompt_config);
     parse_sections_config(&mut prompt, sections_config);
+    if let Some(lines_config) = config_table.get("lines") {
+        parse_lines_config(&mut prompt, lines_config);
+    }
+    if let Some(transient_config) = config_table.get("transient") {
+        prompt.transient = Some(parse_section(&prompt, "transient", transient_config));
+    }
+    if let Some(continuation_config) = config_table.get("continuation") {
+        prompt.continuation = Some(parse_section(&prompt, "continuation", continuation_config));
+    }
+    if let Some(select_config) = config_table.get("select") {
+        prompt.select = Some(parse_section(&prompt, "select", select_config));
+    }
+    if let Some(trace_config) = config_table.get("trace") {
+        prompt.trace = Some(parse_section(&prompt, "trace", trace_config));
+    }
     prompt
 }
 
-fn read_config(path: &str) -> Map<String, Value> {
+fn read_config(path: &str) -> String {
     let mut file = File::open(path).expect("Failed to open file");
     let mut contents = String::new();
     file.read_to_string(&mut contents)
         .expect("Failed to read file");
-    contents.parse::<toml::Table>().unwrap()
+    contents
 }
 
 fn parse_sections_config(prompt: &mut Prompt, properties: &Value) {
     if !properties.is_table() {
         panic!("Unknown value in config");
     }
-    for (_section, value) in properties.as_table().unwrap() {
-        let section_values = value.as_table().unwrap();
-        prompt.sections.push(prompt::PromptSection {
-            text: match section_values.get_key_value("text") {
-                Some(text) => {
-                    let text_string = text.1.as_str().expect("sections.text must be a string").to_string();
-                    let mut text_chars = text_string.chars();
-                    if text_chars.next().is_some_and(|c| c == '$') {
-                        let env_str: String = text_chars.collect();
-                        match env::var(env_str) {
-                            Ok(env_value) => env_value,
-                            _err => String::new(),
-                        }
-                    }
-                    else {
-                        text_string
-                    }
-                },
-                None => String::new(),
-            },
-            path: match section_values.get_key_value("path") {
-                Some(path) => {
-                    let path_string = path.1.as_str().expect("sections.path must be a string").to_string();
-                    let mut path_chars = path_string.chars();
-                    if path_chars.next().is_some_and(|c| c == '$') {
-                        let env_str: String = path_chars.collect();
-                        env::var(env_str).expect("sections.path values that start with '$' must be valid environment variables")
-                    }
-                    else {
-                        path_string
-                    }
-                },
-                None => String::new(),
-            },
-            icon: match section_values.get_key_value("icon") {
-                Some(icon) => icon
+    for (section, value) in properties.as_table().unwrap() {
+        let section = parse_section(prompt, section, value);
+        prompt.sections.push(section);
+    }
+    prompt.sections.sort_by_key(|k| k.order);
+}
+
+fn parse_section(prompt: &Prompt, name: &str, value: &Value) -> prompt::PromptSection {
+    let section_values = value.as_table().expect("sections must be tables");
+    prompt::PromptSection {
+        name: name.to_string(),
+        segment: match section_values.get_key_value("type") {
+            Some(segment) => segment
+                .1
+                .as_str()
+                .expect("sections.type must be a string")
+                .to_string(),
+            None => "env".to_string(),
+        },
+        segment_config: section_values.clone(),
+        text: match section_values.get_key_value("text") {
+            Some(text) => text
+                .1
+                .as_str()
+                .expect("sections.text must be a string")
+                .to_string(),
+            None => String::new(),
+        },
+        path: match section_values.get_key_value("path") {
+            Some(path) => {
+                path::SectionPath::from(path.1.as_str().expect("sections.path must be a string"))
+            }
+            None => Default::default(),
+        },
+        source_path: String::new(),
+        path_info: Default::default(),
+        root: String::new(),
+        root_markers: match section_values.get_key_value("root_markers") {
+            Some(root_markers) => root_markers
+                .1
+                .as_array()
+                .expect("sections.root_markers must be an array of strings")
+                .iter()
+                .map(|marker| {
+                    marker
+                        .as_str()
+                        .expect("sections.root_markers must be an array of strings")
+                        .to_string()
+                })
+                .collect(),
+            None => vec![".git".to_string()],
+        },
+        shorten: match section_values.get_key_value("shorten") {
+            Some(shorten) => path::Shorten::from_str(
+                shorten
                     .1
                     .as_str()
-                    .expect("sections.icon must be a string")
-                    .to_string(),
-                None => String::new(),
-            },
-            format: match section_values.get_key_value("format") {
-                Some(format) => format
+                    .expect("sections.shorten must be 'first_char' or 'unique'"),
+            )
+            .expect("sections.shorten must be 'first_char' or 'unique'"),
+            None => Default::default(),
+        },
+        substitutions: match section_values.get_key_value("substitutions") {
+            Some(substitutions) => parse_substitutions(substitutions.1),
+            None => Vec::new(),
+        },
+        icon: match section_values.get_key_value("icon") {
+            Some(icon) => icon
+                .1
+                .as_str()
+                .expect("sections.icon must be a string")
+                .to_string(),
+            None => String::new(),
+        },
+        readonly_icon: match section_values.get_key_value("readonly_icon") {
+            Some(readonly_icon) => readonly_icon
+                .1
+                .as_str()
+                .expect("sections.readonly_icon must be a string")
+                .to_string(),
+            None => String::new(),
+        },
+        missing_icon: match section_values.get_key_value("missing_icon") {
+            Some(missing_icon) => missing_icon
+                .1
+                .as_str()
+                .expect("sections.missing_icon must be a string")
+                .to_string(),
+            None => String::new(),
+        },
+        symlink_icon: match section_values.get_key_value("symlink_icon") {
+            Some(symlink_icon) => symlink_icon
+                .1
+                .as_str()
+                .expect("sections.symlink_icon must be a string")
+                .to_string(),
+            None => String::new(),
+        },
+        format: match section_values.get_key_value("format") {
+            Some(format) => format
+                .1
+                .as_str()
+                .expect("sections.format must be a string")
+                .to_string(),
+            None => String::new(),
+        },
+        parent_format: match section_values.get_key_value("parent_format") {
+            Some(parent_format) => parent_format
+                .1
+                .as_str()
+                .expect("sections.parent_format must be a string")
+                .to_string(),
+            None => "%p".to_string(),
+        },
+        separator_format: match section_values.get_key_value("separator_format") {
+            Some(separator_format) => separator_format
+                .1
+                .as_str()
+                .expect("sections.separator_format must be a string")
+                .to_string(),
+            None => "%p".to_string(),
+        },
+        basename_format: match section_values.get_key_value("basename_format") {
+            Some(basename_format) => basename_format
+                .1
+                .as_str()
+                .expect("sections.basename_format must be a string")
+                .to_string(),
+            None => "%p".to_string(),
+        },
+        short_format: match section_values.get_key_value("short_format") {
+            Some(short_format) => short_format
+                .1
+                .as_str()
+                .expect("sections.short_format must be a string")
+                .to_string(),
+            None => String::new(),
+        },
+        min_format: match section_values.get_key_value("min_format") {
+            Some(min_format) => min_format
+                .1
+                .as_str()
+                .expect("sections.min_format must be a string")
+                .to_string(),
+            None => String::new(),
+        },
+        visible: match section_values.get_key_value("visible") {
+            Some(visible) => visible
+                .1
+                .as_bool()
+                .expect("sections.visible must be a bool"),
+            None => true,
+        },
+        priority: match section_values.get_key_value("priority") {
+            Some(priority) => usize::try_from(
+                priority
+                    .1
+                    .as_integer()
+                    .expect("prompt.section_pad must be a positive integer"),
+            )
+            .expect("prompt.section_pad must be a positive integer"),
+            None => 15,
+        },
+        foreground: match section_values.get_key_value("foreground") {
+            Some(foreground) => {
+                let foreground_str = foreground
                     .1
                     .as_str()
-                    .expect("sections.format must be a string")
-                    .to_string(),
-                None => String::new(),
-            },
-            visible: match section_values.get_key_value("visible") {
-                Some(visible) => visible
+                    .expect("sections.foreground must be a string");
+                prompt
+                    .shell
+                    .foreground
+                    .get_escape(foreground_str)
+                    .unwrap_or_else(|_| foreground_str.to_string())
+            }
+            None => String::new(),
+        },
+        background: match section_values.get_key_value("background") {
+            Some(background) => {
+                let background_str = background
                     .1
-                    .as_bool()
-                    .expect("sections.visible must be a bool"),
-                None => true,
-            },
-            priority: match section_values.get_key_value("priority") {
-                Some(priority) => usize::try_from(
-                    priority
-                        .1
-                        .as_integer()
-                        .expect("prompt.section_pad must be a positive integer"),
-                )
-                .expect("prompt.section_pad must be a positive integer"),
-                None => 15,
-            },
-            foreground: match section_values.get_key_value("foreground") {
-                Some(foreground) => {
-                    let foreground_str = foreground
-                        .1
+                    .as_str()
+                    .expect("sections.background must be a string");
+                prompt
+                    .shell
+                    .background
+                    .get_escape(background_str)
+                    .unwrap_or_else(|_| background_str.to_string())
+            }
+            None => String::new(),
+        },
+        position: match section_values.get_key_value("position") {
+            Some(position) => Position::from_str(position.1.as_str().expect(
+                "sections.position must be 'left', 'right', 'center', 'prompt', or 'rprompt'",
+            ))
+            .expect("sections.position must be 'left', 'right', 'center', 'prompt', or 'rprompt'"),
+            None => Position::LeftAlign,
+        },
+        order: match section_values.get_key_value("order") {
+            Some(order) => {
+                let order_int = order
+                    .1
+                    .as_integer()
+                    .expect("sections.order must be a positive integer");
+                order_int
+                    .try_into()
+                    .expect("section.order must be a positive integer")
+            }
+            None => 0,
+        },
+        options: match section_values.get_key_value("options") {
+            Some(options_value) => {
+                let mut section_options: prompt::SectionOptions = Default::default();
+                for option in options_value
+                    .1
+                    .as_array()
+                    .expect("options must be an array of strings")
+                {
+                    match option
+                        .as_str()
+                        .expect("options must be an array of strings")
+                    {
+                        "not_zero" => section_options.not_zero = true,
+                        "not_empty" => section_options.not_empty = true,
+                        "~" | "tilde" => section_options.tilde = true,
+                        "repo" => section_options.repo = true,
+                        unknown => panic!("Unrecognized option: '{unknown}'"),
+                    };
+                }
+                section_options
+            }
+            None => Default::default(),
+        },
+        when: match section_values.get_key_value("when") {
+            Some(when) => parse_when(prompt, when.1),
+            None => Vec::new(),
+        },
+        truncate: None,
+        fitted: Default::default(),
+        collect_time: Default::default(),
+    }
+}
+
+fn parse_when(prompt: &Prompt, value: &Value) -> Vec<WhenRule> {
+    let rules = value
+        .as_array()
+        .expect("sections.when must be an array of tables");
+    rules
+        .iter()
+        .map(|rule| {
+            let rule = rule
+                .as_table()
+                .expect("sections.when must be an array of tables");
+            let string = |key: &str| {
+                rule.get(key).map(|value| {
+                    value
                         .as_str()
-                        .expect("sections.foreground must be a string");
+                        .unwrap_or_else(|| panic!("sections.when.{key} must be a string"))
+                        .to_string()
+                })
+            };
+            if let Some(unknown) = rule.keys().find(|key| {
+                !["condition", "foreground", "background", "icon", "format"].contains(&key.as_str())
+            }) {
+                panic!("Unknown value '{unknown}' in sections.when");
+            }
+            let condition = string("condition").expect("sections.when needs a condition");
+            WhenRule {
+                condition: condition
+                    .parse()
+                    .unwrap_or_else(|error| panic!("sections.when.condition: {error}")),
+                foreground: string("foreground").map(|foreground| {
                     prompt
                         .shell
                         .foreground
-                        .get_escape(foreground_str)
-                        .unwrap_or_else(|foreground_str| foreground_str.to_string())
-                }
-                None => String::new(),
-            },
-            background: match section_values.get_key_value("background") {
-                Some(background) => {
-                    let background_str = background
-                        .1
-                        .as_str()
-                        .expect("sections.background must be a string");
+                        .get_escape(&foreground)
+                        .unwrap_or(foreground)
+                }),
+                background: string("background").map(|background| {
                     prompt
                         .shell
                         .background
-                        .get_escape(background_str)
-                        .unwrap_or_else(|background_str| background_str.to_string())
-                }
-                None => String::new(),
-            },
-            position: match section_values.get_key_value("position") {
-                Some(position) => Position::from_str(
-                    position
-                        .1
+                        .get_escape(&background)
+                        .unwrap_or(background)
+                }),
+                icon: string("icon"),
+                format: string("format"),
+            }
+        })
+        .collect()
+}
+
+// either a table of prefixes, or an array of tables with a prefix or regex
+fn parse_substitutions(value: &Value) -> Vec<path::Substitution> {
+    if let Some(table) = value.as_table() {
+        return table
+            .iter()
+            .map(|(prefix, replacement)| {
+                path::Substitution::Prefix(
+                    prefix.to_string(),
+                    replacement
                         .as_str()
-                        .expect("sections.position must be 'left', 'right', 'center', or 'prompt'"),
+                        .expect("sections.substitutions values must be strings")
+                        .to_string(),
                 )
-                .expect("sections.position must be 'left', 'right', 'center', or 'prompt'"),
-                None => Position::LeftAlign,
-            },
-            order: match section_values.get_key_value("order") {
-                Some(order) => {
-                    let order_int = order.1.as_integer().expect("sections.order must be a positive integer");
-                    order_int.try_into().expect("section.order must be a positive integer")
+            })
+            .collect();
+    }
+    let mut substitutions = Vec::new();
+    for rule in value
+        .as_array()
+        .expect("sections.substitutions must be a table or an array of tables")
+    {
+        let rule = rule
+            .as_table()
+            .expect("sections.substitutions must be a table or an array of tables");
+        let replacement = rule
+            .get("replace")
+            .and_then(|r| r.as_str())
+            .expect("sections.substitutions rules need a 'replace' string")
+            .to_string();
+        if let Some(prefix) = rule.get("prefix") {
+            substitutions.push(path::Substitution::Prefix(
+                prefix
+                    .as_str()
+                    .expect("sections.substitutions prefix must be a string")
+                    .to_string(),
+                replacement,
+            ));
+        } else if let Some(regex) = rule.get("regex") {
+            substitutions.push(path::Substitution::Regex(
+                Regex::new(
+                    regex
+                        .as_str()
+                        .expect("sections.substitutions regex must be a string"),
+                )
+                .expect("sections.substitutions regex must be a valid regex"),
+                replacement,
+            ));
+        } else {
+            panic!("sections.substitutions rules need a 'prefix' or 'regex'");
+        }
+    }
+    substitutions
+}
+
+fn parse_lines_config(prompt: &mut Prompt, properties: &Value) {
+    let lines = properties
+        .as_array()
+        .expect("lines must be an array of tables ([[lines]])");
+    for line_value in lines {
+        let mut line: PromptLine = Default::default();
+        for (key, value) in line_value
+            .as_table()
+            .expect("lines must be an array of tables ([[lines]])")
+        {
+            match key.as_str() {
+                "sections" => {
+                    for section in value
+                        .as_array()
+                        .expect("lines.sections must be an array of strings")
+                    {
+                        line.sections.push(
+                            section
+                                .as_str()
+                                .expect("lines.sections must be an array of strings")
+                                .to_string(),
+                        );
+                    }
                 }
-                None => 0,
-            },
-            options: match section_values.get_key_value("options") {
-                Some(options_value) => {
-                    let mut section_options: prompt::SectionOptions = Default::default();
-                    for option in options_value.1.as_array().expect("options must be an array of strings") {
-                        match option.as_str().expect("options must be an array of strings") {
-                            "not_zero" => section_options.not_zero = true,
-                            "not_empty" => section_options.not_empty = true,
-                            "~" | "tilde" => section_options.tilde = true,
-                            unknown => panic!("Unrecognized option: '{unknown}'"),
-                        };
-                    };
-                    section_options
-                },
-                None => Default::default(),
+                "section_pad" => {
+                    line.section_pad = Some(
+                        usize::try_from(
+                            value
+                                .as_integer()
+                                .expect("lines.section_pad must be a positive integer"),
+                        )
+                        .expect("lines.section_pad must be a positive integer"),
+                    )
+                }
+                "surround_pad" => {
+                    line.surround_pad = Some(
+                        usize::try_from(
+                            value
+                                .as_integer()
+                                .expect("lines.surround_pad must be a positive integer"),
+                        )
+                        .expect("lines.surround_pad must be a positive integer"),
+                    )
+                }
+                "section_fill" => {
+                    line.section_fill = Some(
+                        value
+                            .as_str()
+                            .expect("lines.section_fill must be a string")
+                            .to_string(),
+                    )
+                }
+                "blank_fill" => {
+                    line.blank_fill = Some(
+                        value
+                            .as_str()
+                            .expect("lines.blank_fill must be a string")
+                            .to_string(),
+                    )
+                }
+                unknown => panic!("Unknown value '{unknown}' in lines"),
             }
-        });
+        }
+        prompt.lines.push(line);
     }
-    prompt.sections.sort_unstable_by_key(|k| k.order);
 }
 
 fn parse_prompt_config(prompt: &mut Prompt, properties: &Value) {
@@ -173,6 +511,17 @@ fn parse_prompt_config(prompt: &mut Prompt, properties: &Value) {
     for (key, value) in properties.as_table().unwrap() {
         match key.as_str() {
             "newline" => prompt.newline = value.as_bool().expect("prompt.newline must be a bool"),
+            "rprompt" => prompt.rprompt = value.as_bool().expect("prompt.rprompt must be a bool"),
+            "ambiguous_wide" => {
+                prompt.width.ambiguous_wide = value
+                    .as_bool()
+                    .expect("prompt.ambiguous_wide must be a bool")
+            }
+            "nerd_font_wide" => {
+                prompt.width.nerd_font_wide = value
+                    .as_bool()
+                    .expect("prompt.nerd_font_wide must be a bool")
+            }
             "section_pad" => {
                 prompt.section_pad = usize::try_from(
                     value
@@ -202,13 +551,13 @@ fn parse_prompt_config(prompt: &mut Prompt, properties: &Value) {
                     .to_string()
             }
             "shell" => {
-                prompt.shell = prompt::shell::ShellInstance::new(
-                    prompt::shell::Shell::from_str(
+                prompt.shell = shell::ShellInstance::new(
+                    shell::Shell::from_str(
                         value
                             .as_str()
-                            .expect("prompt.shell must be 'bash' or 'zsh'"),
+                            .expect("prompt.shell must be 'bash', 'zsh', or 'fish'"),
                     )
-                    .expect("prompt.shell must be 'bash' or 'zsh'"),
+                    .expect("prompt.shell must be 'bash', 'zsh', or 'fish'"),
                 )
             }
             "foreground" => {
@@ -224,10 +573,10 @@ fn parse_prompt_config(prompt: &mut Prompt, properties: &Value) {
         .shell
         .foreground
         .get_escape(foreground)
-        .unwrap_or_else(|foreground| foreground.to_string());
+        .unwrap_or_else(|_| foreground.to_string());
     prompt.background = prompt
         .shell
         .background
         .get_escape(background)
-        .unwrap_or_else(|background| background.to_string());
+        .unwrap_or_else(|_| background.to_string());
 }
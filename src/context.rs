@@ -0,0 +1,156 @@
+use std::{
+    collections::HashMap,
+    env,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// what a prompt is rendered against, so nothing is read from the process while rendering
+#[derive(Clone)]
+pub struct Context {
+    pub columns: usize, // terminal width the prompt is fitted to
+    pub env: HashMap<String, String>,
+    pub cwd: PathBuf,
+    pub status: Option<i32>, // exit status of the last command
+    pub time: SystemTime,
+    pub hostname: String,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context {
+            columns: 80,
+            env: HashMap::new(),
+            cwd: PathBuf::new(),
+            status: None,
+            time: UNIX_EPOCH,
+            hostname: String::new(),
+        }
+    }
+}
+
+impl Context {
+    // the context of the running shell, where the init script exports COLUMNS and RETURN_CODE
+    pub fn from_env() -> Self {
+        let env: HashMap<String, String> = env::vars().collect();
+        Context {
+            columns: env
+                .get("COLUMNS")
+                .and_then(|columns| columns.parse().ok())
+                .unwrap_or(80),
+            status: env
+                .get("RETURN_CODE")
+                .and_then(|status| status.parse().ok()),
+            // PWD keeps the path the user took through symlinks
+            cwd: env
+                .get("PWD")
+                .map(PathBuf::from)
+                .or_else(|| env::current_dir().ok())
+                .unwrap_or_default(),
+            time: SystemTime::now(),
+            hostname: hostname(&env),
+            env,
+        }
+    }
+
+    pub fn var(&self, name: &str) -> Option<&str> {
+        self.env.get(name).map(String::as_str)
+    }
+
+    pub fn home(&self) -> &str {
+        self.var("HOME").unwrap_or_default()
+    }
+
+    // shells rarely export these, so they fall back to the hostname
+    fn lookup(&self, name: &str) -> Option<&str> {
+        match (self.var(name), name) {
+            (None, "HOST" | "HOSTNAME") if !self.hostname.is_empty() => Some(&self.hostname),
+            (value, _) => value,
+        }
+    }
+
+    // shell-like expansion of $VAR, ${VAR}, ${VAR:-default} (when unset or empty), and
+    // ${VAR-default} (when unset), where \$ is a '$' and unset variables are empty
+    pub fn interpolate(&self, value: &str) -> String {
+        let mut interpolated = String::new();
+        let mut chars = value.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next_if(|c| *c == '$' || *c == '\\') {
+                    Some(escaped) => interpolated.push(escaped),
+                    None => interpolated.push('\\'),
+                },
+                '$' if chars.next_if_eq(&'{').is_some() => {
+                    // defaults can hold variables of their own, so braces are matched
+                    let mut expression = String::new();
+                    let mut depth = 1;
+                    for c in chars.by_ref() {
+                        match c {
+                            '{' => depth += 1,
+                            '}' => depth -= 1,
+                            _ => (),
+                        }
+                        if depth == 0 {
+                            break;
+                        }
+                        expression.push(c);
+                    }
+                    interpolated += &self.expand(&expression);
+                }
+                '$' if chars
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphabetic() || *c == '_') =>
+                {
+                    let mut name = String::new();
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                        name.push(c);
+                    }
+                    interpolated += self.lookup(&name).unwrap_or_default();
+                }
+                _ => interpolated.push(c),
+            }
+        }
+        interpolated
+    }
+
+    // what's inside ${...}
+    fn expand(&self, expression: &str) -> String {
+        let name_end = expression
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(expression.len());
+        let (name, rest) = expression.split_at(name_end);
+        let value = self.lookup(name);
+        if let Some(default) = rest.strip_prefix(":-") {
+            match value {
+                Some(value) if !value.is_empty() => value.to_string(),
+                _ => self.interpolate(default),
+            }
+        } else if let Some(default) = rest.strip_prefix('-') {
+            match value {
+                Some(value) => value.to_string(),
+                None => self.interpolate(default),
+            }
+        } else {
+            value.unwrap_or_default().to_string()
+        }
+    }
+}
+
+#[cfg(unix)]
+fn hostname(_env: &HashMap<String, String>) -> String {
+    let mut buf = [0 as libc::c_char; 256];
+    // gethostname only writes into the buffer given to it
+    unsafe {
+        if libc::gethostname(buf.as_mut_ptr(), buf.len() - 1) != 0 {
+            return String::new();
+        }
+        std::ffi::CStr::from_ptr(buf.as_ptr())
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+#[cfg(not(unix))]
+fn hostname(env: &HashMap<String, String>) -> String {
+    env.get("COMPUTERNAME").cloned().unwrap_or_default()
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use crate::{
+    context::Context,
+    prompt::{Fitted, Position, Prompt, PromptSection},
+};
+
+// what happened to a section when the prompt was laid out, and why
+pub struct Explanation {
+    pub name: String,
+    pub segment: String,
+    pub text: String,
+    pub path: String, // after options and shortening
+    pub icon: String,
+    pub width: usize, // columns the section takes, or would take if it were shown
+    pub shown: bool,
+    pub reason: String,
+    pub priority: usize,
+    pub position: Position,
+    pub collect_time: Duration,
+}
+
+impl Prompt<'_> {
+    // lays the prompt out like render does, in the order of the config's sections
+    pub fn explain(&self, context: &Context) -> Vec<Explanation> {
+        let prompt = self.collected(context);
+        let mut main = prompt.clone();
+        main.term_text();
+        // rprompt_text lays its sections out as left aligned, so remember where they came from
+        let mut right = prompt.clone();
+        right.rprompt_text();
+        right
+            .sections
+            .iter_mut()
+            .for_each(|s| s.position = Position::RPrompt);
+
+        let errors = prompt.errors.lock().unwrap().clone();
+        self.sections
+            .iter()
+            .filter_map(|section| {
+                let (laid_out, section) = [&main, &right].into_iter().find_map(|laid_out| {
+                    let found = laid_out.sections.iter().find(|s| s.name == section.name)?;
+                    Some((laid_out, found))
+                })?;
+                let (shown, reason) = visibility(section, context.columns, &errors);
+                Some(Explanation {
+                    name: section.name.clone(),
+                    segment: section.segment.clone(),
+                    text: section.text.clone(),
+                    path: section.path.to_string(),
+                    icon: section.icon.clone(),
+                    width: laid_out.section_width(section),
+                    shown,
+                    reason,
+                    priority: section.priority,
+                    position: section.position,
+                    collect_time: section.collect_time,
+                })
+            })
+            .collect()
+    }
+}
+
+// checked in the same order the prompt hides sections in
+fn visibility(section: &PromptSection, columns: usize, errors: &[String]) -> (bool, String) {
+    let hidden = |reason: &str| (false, reason.to_string());
+    if section.fitted == Fitted::Hidden {
+        return (
+            false,
+            format!("hidden by fit_prompt, the prompt doesn't fit in {columns} columns"),
+        );
+    }
+    if section.options.not_zero && section.text == "0" {
+        return hidden("hidden by not_zero, the text is 0");
+    }
+    if section.options.not_empty
+        && section.text.is_empty()
+        && section.path.is_empty()
+        && section.root.is_empty()
+    {
+        return hidden("hidden by not_empty, the text and path are empty");
+    }
+    if !section.visible {
+        // an unknown type or key is reported with the section's name in front
+        let prefix = format!("section '{}': unknown ", section.name);
+        if let Some(error) = errors.iter().find(|error| error.starts_with(&prefix)) {
+            return hidden(&format!(
+                "hidden, {}",
+                &error[prefix.len() - "unknown ".len()..]
+            ));
+        }
+        return match section.segment_config.get("visible") {
+            Some(_) => hidden("hidden by visible = false"),
+            None => hidden("hidden by its segment"),
+        };
+    }
+    if section.format.is_empty() {
+        return hidden("hidden, the format is empty");
+    }
+    if section.fitted == Fitted::Shortened {
+        return (
+            true,
+            format!("shortened by fit_prompt to fit in {columns} columns"),
+        );
+    }
+    (true, "shown".to_string())
+}
@@ -0,0 +1,149 @@
+use std::{env, path::Path};
+
+use unprompt::shell::Shell;
+
+const ZSH_INIT: &str = r#"_unprompt() {
+    {unprompt} "$@"
+}
+
+_unprompt_precmd() {
+    export RETURN_CODE=$?
+    PS1='$(_unprompt)'
+    RPROMPT='$(_unprompt --rprompt)'
+}
+
+# collapse the accepted prompt before the command runs
+_unprompt_line_finish() {
+    PS1="$(_unprompt --transient)"
+    RPROMPT=''
+    zle .reset-prompt
+}
+
+# keep the shell's own secondary prompts unless the config defines them
+_unprompt_secondary() {
+    local text
+    text="$(_unprompt "$2")"
+    [[ -n $text ]] && typeset -g "$1=$text"
+}
+
+setopt promptsubst
+export COLUMNS
+_unprompt_secondary PS2 --continuation
+_unprompt_secondary PS3 --select
+_unprompt_secondary PS4 --trace
+autoload -Uz add-zsh-hook
+add-zsh-hook precmd _unprompt_precmd
+zle -N zle-line-finish _unprompt_line_finish
+"#;
+
+const BASH_INIT: &str = r#"_unprompt() {
+    {unprompt} "$@"
+}
+
+_unprompt_prompt_command() {
+    export RETURN_CODE=$?
+    PS1="$(_unprompt)"
+    _unprompt_history="$(HISTTIMEFORMAT='' history 1)"
+}
+
+# bash can't redraw an accepted line, so PS0 moves back over the prompt and the command
+# and writes the command again after the transient prompt
+_unprompt_transient() {
+    local entry command shown line transient rows=0
+    entry="$(HISTTIMEFORMAT='' history 1)"
+    # lines left out of the history, like empty ones, keep the full prompt
+    [[ $entry == "$_unprompt_history" ]] && return
+    command="${entry#*[0-9]  }"
+    command="${command//$'\n'/$'\n'$PS2}"
+    # \001 and \002 mark escapes for readline and take no columns
+    shown="${PS1//[$'\001\002']/}$command"
+    while [[ $shown =~ $'\e'\[[0-9\;]*m ]]; do
+        shown="${shown//"${BASH_REMATCH[0]}"/}"
+    done
+    while IFS= read -r line; do
+        (( rows += ${#line} > 0 ? (${#line} - 1) / ${COLUMNS:-80} + 1 : 1 ))
+    done <<< "$shown"
+    transient="$(_unprompt --transient)"
+    # the \r keeps PS0's command substitution from dropping the newline, and the markers
+    # would be written out, since only readline reads them
+    printf '\e[%dA\r\e[J%s%s\n\r' "$rows" "${transient//[$'\001\002']/}" "$command"
+}
+
+# keep the shell's own secondary prompts unless the config defines them
+_unprompt_secondary() {
+    local text
+    text="$(_unprompt "$2")"
+    # readline only draws PS1 and PS2, the others are written out as they are
+    [[ $1 == PS[34] ]] && text="${text//[$'\001\002']/}"
+    [[ -n $text ]] && printf -v "$1" '%s' "$text"
+}
+
+export COLUMNS
+_unprompt_secondary PS2 --continuation
+_unprompt_secondary PS3 --select
+_unprompt_secondary PS4 --trace
+PS0='$(_unprompt_transient)'
+PROMPT_COMMAND="_unprompt_prompt_command${PROMPT_COMMAND:+; $PROMPT_COMMAND}"
+"#;
+
+const FISH_INIT: &str = r#"function _unprompt
+    {unprompt} $argv
+end
+
+function fish_prompt
+    set -lx RETURN_CODE $status
+    set -lx COLUMNS $COLUMNS
+    if set -q _unprompt_transient
+        printf '\e[J%s' (_unprompt --transient | string collect)
+    else
+        printf '%s' (_unprompt | string collect)
+    end
+end
+
+function fish_right_prompt
+    set -lx COLUMNS $COLUMNS
+    # fish draws the right prompt last, so the transient redraw ends here
+    if set -q _unprompt_transient
+        set -e _unprompt_transient
+    else
+        printf '%s' (_unprompt --rprompt | string collect)
+    end
+end
+
+# collapse the prompt when the command is complete, otherwise enter adds a line
+function _unprompt_execute
+    if commandline --is-valid; or not commandline | string length -q
+        if not commandline --paging-mode
+            set -g _unprompt_transient
+            commandline -f repaint
+        end
+    end
+    commandline -f execute
+end
+
+bind \r _unprompt_execute
+bind -M insert \r _unprompt_execute
+"#;
+
+// shell code that sets up the prompt, used with `eval "$(unprompt init zsh)"`
+pub fn init_script(shell: Shell, config_path: Option<&str>) -> String {
+    let executable = env::current_exe().expect("Failed to find the unprompt executable");
+    let mut command = quote(&executable.to_string_lossy());
+    if let Some(path) = config_path {
+        let path = Path::new(path)
+            .canonicalize()
+            .expect("Failed to find config file");
+        command += " -c ";
+        command += &quote(&path.to_string_lossy());
+    }
+    match shell {
+        Shell::Zsh => ZSH_INIT.replace("{unprompt}", &command),
+        Shell::Bash => BASH_INIT.replace("{unprompt}", &command),
+        Shell::Fish => FISH_INIT.replace("{unprompt}", &command),
+    }
+}
+
+// single quotes keep the shell from expanding anything inside the path
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
@@ -0,0 +1,123 @@
+// picks how every section is drawn so the line fits, keeping as much priority as possible
+
+pub struct Candidate {
+    pub group: usize,       // sections in a group share pads, one group per alignment
+    pub priority: usize,    // kept when the section is drawn in any rendering
+    pub widths: Vec<usize>, // renderings from full to shortest, hiding is always possible
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Score {
+    priority: usize, // total priority of the drawn sections
+    cost: usize,     // how far the drawn sections were shortened, weighted by priority
+}
+
+impl Score {
+    fn is_better(&self, other: Option<Score>) -> bool {
+        other.is_none_or(|other| {
+            self.priority > other.priority
+                || (self.priority == other.priority && self.cost < other.cost)
+        })
+    }
+}
+
+// where a state was reached from
+#[derive(Clone, Copy)]
+struct Step {
+    mask: usize,
+    width: usize,
+    rendering: Option<usize>,
+}
+
+pub const GROUPS: usize = 3;
+
+// chooses a rendering for each candidate (None hides it) so the total width fits in columns
+// group_pad is added once for every drawn group, section_pad between sections in the same group
+pub fn solve(
+    candidates: &[Candidate],
+    group_pad: usize,
+    section_pad: usize,
+    columns: usize,
+) -> Vec<Option<usize>> {
+    let masks = 1 << GROUPS;
+    // best[mask][width]: the best score using `width` columns with the groups in `mask` drawn
+    let mut best: Vec<Vec<Option<Score>>> = vec![vec![None; columns + 1]; masks];
+    let mut steps: Vec<Vec<Vec<Option<Step>>>> = Vec::with_capacity(candidates.len());
+    best[0][0] = Some(Score {
+        priority: 0,
+        cost: 0,
+    });
+
+    for candidate in candidates {
+        let group_bit = 1 << candidate.group;
+        let mut next_best: Vec<Vec<Option<Score>>> = vec![vec![None; columns + 1]; masks];
+        let mut next_steps: Vec<Vec<Option<Step>>> = vec![vec![None; columns + 1]; masks];
+        for mask in 0..masks {
+            for width in 0..=columns {
+                let Some(score) = best[mask][width] else {
+                    continue;
+                };
+                // hidden
+                if score.is_better(next_best[mask][width]) {
+                    next_best[mask][width] = Some(score);
+                    next_steps[mask][width] = Some(Step {
+                        mask,
+                        width,
+                        rendering: None,
+                    });
+                }
+                let pad = if mask & group_bit == 0 {
+                    group_pad
+                } else {
+                    section_pad
+                };
+                for (rendering, rendering_width) in candidate.widths.iter().enumerate() {
+                    let next_width = width + pad + rendering_width;
+                    if next_width > columns {
+                        continue;
+                    }
+                    let next_score = Score {
+                        priority: score.priority + candidate.priority,
+                        cost: score.cost + rendering * candidate.priority,
+                    };
+                    let next_mask = mask | group_bit;
+                    if next_score.is_better(next_best[next_mask][next_width]) {
+                        next_best[next_mask][next_width] = Some(next_score);
+                        next_steps[next_mask][next_width] = Some(Step {
+                            mask,
+                            width,
+                            rendering: Some(rendering),
+                        });
+                    }
+                }
+            }
+        }
+        best = next_best;
+        steps.push(next_steps);
+    }
+
+    // find the best final state, then walk the steps back to the start
+    let mut end: Option<(usize, usize)> = None;
+    let mut end_score: Option<Score> = None;
+    for (mask, widths) in best.iter().enumerate() {
+        for (width, score) in widths.iter().enumerate() {
+            if let Some(score) = score
+                && score.is_better(end_score)
+            {
+                end_score = Some(*score);
+                end = Some((mask, width));
+            }
+        }
+    }
+    let mut chosen = vec![None; candidates.len()];
+    let Some((mut mask, mut width)) = end else {
+        return chosen;
+    };
+    for (candidate_i, candidate_steps) in steps.iter().enumerate().rev() {
+        let step = candidate_steps[mask][width].expect("every reached state has a step");
+        chosen[candidate_i] = step.rendering;
+        mask = step.mask;
+        width = step.width;
+    }
+    chosen
+}
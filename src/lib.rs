@@ -0,0 +1,26 @@
+//! Renders shell prompts from a TOML configuration.
+//!
+//! Load a config with [`parse_config`] or [`parse_config_str`], then render it with
+//! [`Prompt::render`].
+
+mod cache;
+pub mod condition;
+pub mod config;
+pub mod context;
+pub mod explain;
+mod layout;
+pub mod path;
+mod plugin;
+pub mod prompt;
+pub mod segment;
+pub mod shell;
+#[cfg(feature = "wasm")]
+mod wasm;
+pub mod width;
+
+pub use config::{parse_config, parse_config_str};
+pub use context::Context;
+pub use explain::Explanation;
+pub use prompt::{Fitted, Position, Prompt, PromptSection, RenderTimings, RenderedPrompt};
+pub use segment::{Segment, SegmentData};
+pub use shell::Shell;
@@ -1,7 +1,10 @@
-use std::env;
+use std::{env, str::FromStr};
 
-#[path = "./config.rs"]
-mod config;
+use unprompt::{Context, Explanation, RenderedPrompt, Shell};
+
+mod bench;
+mod init;
+mod preview;
 
 pub enum Theme {
     Default,
@@ -10,8 +13,24 @@ pub enum Theme {
     Pureline,
 }
 
+// which prompt string to print
+enum Mode {
+    Prompt,
+    RPrompt,
+    Transient,
+    Continuation,
+    Select,
+    Trace,
+    Init(Shell),
+    Bench,
+}
+
 fn main() {
     let mut config: Option<String> = None;
+    let mut mode = Mode::Prompt;
+    let mut preview: Option<preview::Preview> = None;
+    let mut explain = false;
+    let mut runs: Option<usize> = None;
 
     let mut args = env::args().skip(1);
     while let Some(arg) = args.next() {
@@ -19,6 +38,57 @@ fn main() {
             "-c" | "--config" => {
                 config = Some(parse_opt(&arg, args.next()));
             }
+            "-r" | "--rprompt" => mode = Mode::RPrompt,
+            "-t" | "--transient" => mode = Mode::Transient,
+            "--continuation" => mode = Mode::Continuation,
+            "--select" => mode = Mode::Select,
+            "--trace" => mode = Mode::Trace,
+            "init" => {
+                mode = Mode::Init(
+                    Shell::from_str(&parse_opt(&arg, args.next()))
+                        .expect("init shell must be 'bash', 'zsh', or 'fish'"),
+                );
+            }
+            "preview" => preview = Some(preview::Preview::default()),
+            "explain" => {
+                explain = true;
+                preview = Some(preview::Preview::default());
+            }
+            "bench" => {
+                mode = Mode::Bench;
+                preview = Some(preview::Preview::default());
+            }
+            "-n" => {
+                runs = Some(
+                    parse_opt(&arg, args.next())
+                        .parse()
+                        .expect("-n must be a number"),
+                )
+            }
+            "--columns" | "--status" | "--cwd" | "--env" | "--sweep" => {
+                let preview = preview
+                    .as_mut()
+                    .unwrap_or_else(|| panic!("{arg} is only for preview, explain, and bench"));
+                match arg.to_lowercase().as_str() {
+                    "--columns" => {
+                        preview.columns = Some(
+                            parse_opt(&arg, args.next())
+                                .parse()
+                                .expect("--columns must be a number"),
+                        )
+                    }
+                    "--status" => {
+                        preview.status = Some(
+                            parse_opt(&arg, args.next())
+                                .parse()
+                                .expect("--status must be a number"),
+                        )
+                    }
+                    "--cwd" => preview.cwd = Some(parse_opt(&arg, args.next())),
+                    "--env" => preview.env.push(parse_opt(&arg, args.next())),
+                    _ => preview.sweep = true,
+                }
+            }
             _ => panic!("Unknown arg: '{arg}'"),
         };
         fn parse_opt(arg: &String, opt: Option<String>) -> String {
@@ -28,7 +98,82 @@ fn main() {
             }
         }
     }
-    let mut prompt = config::parse_config(config.as_deref());
-    prompt.columns = env::var("COLUMNS").unwrap().parse::<usize>().unwrap();
-    println!("{}", prompt.term_text());
+    if runs.is_some() && !matches!(mode, Mode::Bench) {
+        panic!("-n is only for bench");
+    }
+    if let Mode::Init(shell) = mode {
+        print!("{}", init::init_script(shell, config.as_deref()));
+        return;
+    }
+    if matches!(mode, Mode::Bench) {
+        let context = preview.unwrap_or_default().context();
+        bench::bench(config.as_deref(), &context, runs.unwrap_or(100));
+        return;
+    }
+    let prompt = unprompt::parse_config(config.as_deref());
+    if let Some(preview) = &preview
+        && explain
+    {
+        let context = preview.context();
+        for explanation in prompt.explain(&context) {
+            print_explanation(&explanation);
+        }
+        return;
+    }
+    if let Some(preview) = preview {
+        let context = preview.context();
+        for columns in preview.columns(&context) {
+            let rendered = prompt.render(&Context {
+                columns,
+                ..context.clone()
+            });
+            if preview.sweep {
+                println!("\x1B[2m{columns} columns:\x1B[0m");
+            }
+            // the right prompt only goes with the main prompt
+            let rprompt = match mode {
+                Mode::Prompt => rendered.rprompt.as_str(),
+                _ => "",
+            };
+            println!(
+                "{}\x1B[0m",
+                preview::terminal_text(&prompt, mode_text(&rendered, &mode), rprompt, columns)
+            );
+            for error in &rendered.errors {
+                eprintln!("{error}");
+            }
+        }
+        return;
+    }
+    let context = Context::from_env();
+    let rendered = prompt.render(&context);
+    println!("{}", mode_text(&rendered, &mode));
+}
+
+fn print_explanation(explanation: &Explanation) {
+    println!(
+        "{}: {} ({}, priority {})",
+        explanation.name, explanation.reason, explanation.position, explanation.priority
+    );
+    println!("  text: {:?}", explanation.text);
+    println!("  path: {:?}", explanation.path);
+    println!("  icon: {:?}", explanation.icon);
+    println!("  width: {}", explanation.width);
+    println!(
+        "  collected by {} in {:.2} ms",
+        explanation.segment,
+        explanation.collect_time.as_secs_f64() * 1000.0
+    );
+}
+
+fn mode_text<'r>(rendered: &'r RenderedPrompt, mode: &Mode) -> &'r str {
+    match mode {
+        Mode::Prompt => &rendered.prompt,
+        Mode::RPrompt => &rendered.rprompt,
+        Mode::Transient => &rendered.transient,
+        Mode::Continuation => &rendered.continuation,
+        Mode::Select => &rendered.select,
+        Mode::Trace => &rendered.trace,
+        Mode::Init(_) | Mode::Bench => "",
+    }
 }
@@ -0,0 +1,230 @@
+use std::{fmt, fs, path::Path, str::FromStr};
+
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+
+// a displayed path, kept as its components so each part can be styled
+#[derive(Default, Clone, PartialEq)]
+pub struct SectionPath {
+    pub components: Vec<String>, // an absolute path starts with an empty component
+}
+
+impl SectionPath {
+    pub fn is_empty(&self) -> bool {
+        self.components.len() <= 1 && self.components.iter().all(|c| c.is_empty())
+    }
+}
+
+impl From<&str> for SectionPath {
+    fn from(path: &str) -> Self {
+        SectionPath {
+            components: path.split('/').map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+impl fmt::Display for SectionPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.components.join("/"))
+    }
+}
+
+// what the filesystem says about a section's path
+#[derive(Clone)]
+pub struct PathInfo {
+    pub writable: bool,
+    pub exists: bool, // false when the directory was deleted under the shell
+    pub symlink_target: Option<String>, // the resolved path when the path goes through a symlink
+}
+
+impl Default for PathInfo {
+    fn default() -> Self {
+        PathInfo {
+            writable: true,
+            exists: true,
+            symlink_target: None,
+        }
+    }
+}
+
+impl PathInfo {
+    pub fn new(path: &str) -> Self {
+        let path = Path::new(path);
+        let exists = path.exists();
+        PathInfo {
+            // a missing directory is already reported as missing
+            writable: !exists || is_writable(path),
+            exists,
+            symlink_target: fs::canonicalize(path)
+                .ok()
+                .filter(|target| target != path)
+                .map(|target| target.to_string_lossy().to_string()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_writable(path: &Path) -> bool {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // access checks the permissions of the current user, not just the mode bits
+    unsafe { libc::access(c_path.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_writable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| !metadata.permissions().readonly())
+}
+
+// rewrites the displayed path before it is shortened
+#[derive(Clone)]
+pub enum Substitution {
+    Prefix(String, String), // a leading '~' in the prefix stands for $HOME
+    Regex(Regex, String),   // the replacement can use captures ($1)
+}
+
+impl Substitution {
+    // the tilde option is the prefix rule '~' = '~'
+    pub fn tilde() -> Self {
+        Substitution::Prefix("~".to_string(), "~".to_string())
+    }
+
+    pub fn apply(&self, path: &str, home: &str) -> Option<String> {
+        match self {
+            Substitution::Prefix(prefix, replacement) => {
+                let prefix = match prefix.strip_prefix('~') {
+                    // without a home directory, '~' can't match anything
+                    Some(_) if home.is_empty() => return None,
+                    Some(rest) => format!("{home}{rest}"),
+                    None => prefix.to_string(),
+                };
+                let rest = path.strip_prefix(&prefix)?;
+                // only match whole directories
+                if !rest.is_empty() && !rest.starts_with('/') {
+                    return None;
+                }
+                Some(format!("{replacement}{rest}"))
+            }
+            Substitution::Regex(regex, replacement) => regex
+                .is_match(path)
+                .then(|| regex.replace(path, replacement.as_str()).to_string()),
+        }
+    }
+}
+
+// how directories are abbreviated when a path is shortened
+#[derive(Default, Clone, Copy)]
+pub enum Shorten {
+    #[default]
+    FirstChar, // the first character of every directory
+    Unique, // the shortest prefix that no sibling directory shares
+}
+
+impl FromStr for Shorten {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "first_char" => Ok(Shorten::FirstChar),
+            "unique" => Ok(Shorten::Unique),
+            _ => Err(()),
+        }
+    }
+}
+
+// drops '.', '..' and repeated or trailing slashes without looking at the filesystem, like
+// the shell's logical $PWD, so that the path matches what canonicalize and ancestors see
+pub fn normalize(path: &str) -> String {
+    if path.is_empty() {
+        return String::new();
+    }
+    let absolute = path.starts_with('/');
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." if components.last().is_some_and(|last| *last != "..") => {
+                components.pop();
+            }
+            // '..' at the root is the root
+            ".." if absolute => {}
+            _ => components.push(component),
+        }
+    }
+    match (absolute, components.is_empty()) {
+        (true, _) => format!("/{}", components.join("/")),
+        (false, true) => ".".to_string(),
+        (false, false) => components.join("/"),
+    }
+}
+
+// the nearest directory at or above path that contains one of the markers
+pub fn find_root<'p>(path: &'p str, markers: &[String]) -> Option<&'p str> {
+    Path::new(path)
+        .ancestors()
+        .filter(|dir| dir.parent().is_some())
+        .find(|dir| markers.iter().any(|marker| dir.join(marker).exists()))
+        .and_then(Path::to_str)
+}
+
+// abbreviates every component except the last long_levels
+// source_path is the path on disk, which may differ from the displayed path at the start (~)
+pub fn shorten_path(
+    path: &SectionPath,
+    source_path: &str,
+    long_levels: usize,
+    shorten: Shorten,
+) -> SectionPath {
+    let mut components = path.components.clone();
+    let short_levels = components.len().saturating_sub(long_levels);
+    // walk up the source path alongside the displayed components
+    let mut source_dir = Some(Path::new(source_path));
+    for (component_i, component) in components.iter_mut().enumerate().rev() {
+        let dir = source_dir;
+        source_dir = source_dir.and_then(Path::parent);
+        if component_i >= short_levels {
+            continue;
+        }
+        *component = match shorten {
+            Shorten::FirstChar => first_char(component),
+            Shorten::Unique => dir
+                .filter(|dir| {
+                    dir.file_name()
+                        .is_some_and(|name| name == component.as_str())
+                })
+                .and_then(unique_prefix)
+                .unwrap_or_else(|| first_char(component)),
+        };
+    }
+    SectionPath { components }
+}
+
+fn first_char(component: &str) -> String {
+    component
+        .graphemes(true)
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn unique_prefix(dir: &Path) -> Option<String> {
+    let name = dir.file_name()?.to_str()?;
+    let siblings: Vec<String> = fs::read_dir(dir.parent()?)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|sibling| sibling != name)
+        .collect();
+    let mut prefix = String::new();
+    for grapheme in name.graphemes(true) {
+        prefix += grapheme;
+        // a lone dot doesn't tell hidden directories apart
+        if prefix != "." && !siblings.iter().any(|s| s.starts_with(&prefix)) {
+            break;
+        }
+    }
+    Some(prefix)
+}
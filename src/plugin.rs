@@ -0,0 +1,172 @@
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde_json::{Value, json};
+use toml::Table;
+
+use crate::{
+    context::Context,
+    segment::{Segment, SegmentData, section_timeout},
+};
+
+// runs `unprompt-plugin-<plugin>` (or `command`), which reads the context as JSON on stdin
+// and answers with the section's data as JSON on stdout
+pub struct PluginSegment;
+
+impl Segment for PluginSegment {
+    fn name(&self) -> &str {
+        "plugin"
+    }
+
+    fn keys(&self) -> &[&str] {
+        &["plugin", "command", "env"]
+    }
+
+    fn collect(&self, config: &Table, context: &Context) -> SegmentData {
+        // a plugin that fails hides its section instead of breaking the prompt
+        run_plugin(config, context).unwrap_or_else(SegmentData::hidden)
+    }
+
+    fn stops_at_timeout(&self) -> bool {
+        true
+    }
+}
+
+fn run_plugin(config: &Table, context: &Context) -> Option<SegmentData> {
+    let command = match (config.get("command"), config.get("plugin")) {
+        (Some(command), _) => expand_home(
+            command.as_str().expect("sections.command must be a string"),
+            context,
+        ),
+        (None, Some(plugin)) => format!(
+            "unprompt-plugin-{}",
+            plugin.as_str().expect("sections.plugin must be a string")
+        ),
+        (None, None) => panic!("plugin sections need a 'plugin' or 'command'"),
+    };
+
+    let deadline = section_timeout(config).map(|timeout| Instant::now() + timeout);
+    let mut command = Command::new(command);
+    command
+        .current_dir(&context.cwd)
+        // PATH so the plugin can run other programs, and otherwise only what env lists
+        .env_clear()
+        .envs(
+            env_names(config)
+                .into_iter()
+                .chain(["PATH".to_string()])
+                .filter_map(|var| {
+                    context
+                        .var(&var)
+                        .map(|value| (var.clone(), value.to_string()))
+                }),
+        )
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    // a group of its own, so stopping the plugin also stops what it started
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let mut child = command.spawn().ok()?;
+
+    // the pipes are handled on a thread, so a plugin that never answers can still be stopped
+    let (mut stdin, mut stdout) = (child.stdin.take()?, child.stdout.take()?);
+    let request = request_json(config, context).to_string();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        // a plugin that exits without reading the request can still have answered
+        let _ = stdin.write_all(request.as_bytes());
+        drop(stdin);
+        let mut output = Vec::new();
+        let _ = sender.send(stdout.read_to_end(&mut output).map(|_| output));
+    });
+    let output = match deadline {
+        Some(deadline) => receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+        None => receiver.recv().map_err(mpsc::RecvTimeoutError::from),
+    };
+    let (Ok(Ok(output)), Some(status)) = (output, wait(&mut child, deadline)) else {
+        stop(&mut child);
+        return None;
+    };
+    if !status.success() {
+        return None;
+    }
+    let response: Value = serde_json::from_slice(&output).ok()?;
+    Some(SegmentData::from_json(&response))
+}
+
+// a plugin can close its output and keep running, so the deadline holds for its exit too
+fn wait(child: &mut Child, deadline: Option<Instant>) -> Option<ExitStatus> {
+    let Some(deadline) = deadline else {
+        return child.wait().ok();
+    };
+    loop {
+        if let Some(status) = child.try_wait().ok()? {
+            return Some(status);
+        }
+        if Instant::now() >= deadline {
+            return None;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+// kills the plugin along with its group, then reaps it so nothing outlives the prompt
+fn stop(child: &mut Child) {
+    #[cfg(unix)]
+    // the group was created for the plugin, so this can't reach other processes
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    #[cfg(not(unix))]
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+pub(crate) fn expand_home(path: &str, context: &Context) -> String {
+    match path.strip_prefix("~/") {
+        Some(rest) => format!("{}/{rest}", context.home()),
+        None => path.to_string(),
+    }
+}
+
+// the environment variables a plugin is allowed to see
+pub(crate) fn env_names(config: &Table) -> Vec<String> {
+    match config.get("env") {
+        Some(vars) => vars
+            .as_array()
+            .expect("sections.env must be an array of strings")
+            .iter()
+            .map(|var| {
+                var.as_str()
+                    .expect("sections.env must be an array of strings")
+                    .to_string()
+            })
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+pub(crate) fn request_json(config: &Table, context: &Context) -> Value {
+    let env_subset: BTreeMap<String, String> = env_names(config)
+        .into_iter()
+        .filter_map(|var| {
+            context
+                .var(&var)
+                .map(|value| (var.clone(), value.to_string()))
+        })
+        .collect();
+    json!({
+        "cwd": context.cwd,
+        "columns": context.columns,
+        "status": context.status,
+        "env": env_subset,
+        "config": config,
+    })
+}
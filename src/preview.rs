@@ -0,0 +1,142 @@
+use std::path::PathBuf;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unprompt::{Context, Prompt, Shell};
+
+// the sweep shows how the prompt gives way as the terminal narrows
+const SWEEP_COLUMNS: [usize; 9] = [40, 60, 80, 100, 120, 140, 160, 180, 200];
+
+// context values given on the command line, the rest comes from the running shell
+#[derive(Default)]
+pub struct Preview {
+    pub columns: Option<usize>,
+    pub status: Option<i32>,
+    pub cwd: Option<String>,
+    pub env: Vec<String>, // KEY=VALUE
+    pub sweep: bool,
+}
+
+impl Preview {
+    pub fn context(&self) -> Context {
+        let mut context = Context::from_env();
+        for var in &self.env {
+            let (name, value) = var.split_once('=').expect("--env must be KEY=VALUE");
+            context.env.insert(name.to_string(), value.to_string());
+        }
+        // configs read these through $RETURN_CODE and $PWD as well
+        if let Some(status) = self.status {
+            context.status = Some(status);
+            context
+                .env
+                .insert("RETURN_CODE".to_string(), status.to_string());
+        }
+        if let Some(cwd) = &self.cwd {
+            let cwd = match cwd.strip_prefix('~') {
+                Some(rest) => format!("{}{rest}", context.home()),
+                None => cwd.clone(),
+            };
+            context.cwd = PathBuf::from(&cwd);
+            context.env.insert("PWD".to_string(), cwd);
+        }
+        if let Some(columns) = self.columns {
+            context.columns = columns;
+        }
+        context
+    }
+
+    pub fn columns(&self, context: &Context) -> Vec<usize> {
+        match self.sweep {
+            true => SWEEP_COLUMNS.to_vec(),
+            false => vec![context.columns],
+        }
+    }
+}
+
+// what the terminal shows for a prompt string, with rprompt drawn at the right of the last line
+pub fn terminal_text(prompt: &Prompt, text: &str, rprompt: &str, columns: usize) -> String {
+    let text = wrap(&unescape(prompt, text), prompt, columns);
+    let rprompt = unescape(prompt, rprompt);
+    let rprompt_width = visible_width(&rprompt, prompt);
+    let last_line_width = visible_width(text.rsplit('\n').next().unwrap_or_default(), prompt);
+    // like zsh, the right prompt is left out when it doesn't fit
+    if rprompt.is_empty() || last_line_width + rprompt_width >= columns {
+        return text;
+    }
+    // moves the cursor to the column the right prompt starts at
+    format!("{text}\x1B[{}G{rprompt}", columns - rprompt_width + 1)
+}
+
+// zsh wraps escapes in %{ %} and writes '%' as %%, bash wraps them in \001 \002, and the
+// terminal never sees either
+fn unescape(prompt: &Prompt, text: &str) -> String {
+    match prompt.shell.kind {
+        Shell::Bash => return text.replace(['\x01', '\x02'], ""),
+        Shell::Fish => return text.to_string(),
+        Shell::Zsh => (),
+    }
+    let mut unescaped = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('%', Some('{' | '}')) => {
+                chars.next();
+            }
+            ('%', Some('%')) => {
+                chars.next();
+                unescaped.push('%');
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+// breaks lines that are wider than the terminal, where it would wrap them
+fn wrap(text: &str, prompt: &Prompt, columns: usize) -> String {
+    let mut wrapped = String::new();
+    let mut column = 0;
+    for (escape, part) in split_escapes(text) {
+        if escape {
+            wrapped += part;
+            continue;
+        }
+        for grapheme in part.graphemes(true) {
+            if grapheme == "\n" {
+                column = 0;
+            } else {
+                let width = prompt.width.width(grapheme);
+                if column + width > columns {
+                    wrapped.push('\n');
+                    column = 0;
+                }
+                column += width;
+            }
+            wrapped += grapheme;
+        }
+    }
+    wrapped
+}
+
+fn visible_width(text: &str, prompt: &Prompt) -> usize {
+    split_escapes(text)
+        .into_iter()
+        .filter(|(escape, _)| !escape)
+        .map(|(_, part)| prompt.width.width(part))
+        .sum()
+}
+
+// splits text into escape sequences (ESC [ ... final byte) and the text between them
+fn split_escapes(text: &str) -> Vec<(bool, &str)> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("\x1B[") {
+        let end = rest[start + 2..]
+            .find(|c: char| ('@'..='~').contains(&c))
+            .map_or(rest.len(), |end| start + 2 + end + 1);
+        parts.push((false, &rest[..start]));
+        parts.push((true, &rest[start..end]));
+        rest = &rest[end..];
+    }
+    parts.push((false, rest));
+    parts
+}
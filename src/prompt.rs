@@ -1,7 +1,19 @@
-use std::{env, str::FromStr};
+use std::{
+    fmt,
+    iter::Peekable,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-#[path = "./shell.rs"]
-pub mod shell;
+use crate::{
+    condition::WhenRule,
+    config::SECTION_KEYS,
+    context::Context,
+    layout, path,
+    segment::{self, Segment, SegmentData},
+    shell, width,
+};
 
 #[derive(PartialEq, Clone, Copy)]
 pub enum Position {
@@ -9,6 +21,7 @@ pub enum Position {
     CenterAlign = 0,
     RightAlign = 1,
     Prompt = 2,
+    RPrompt = 3, // rendered separately into zsh's RPROMPT
 }
 
 impl FromStr for Position {
@@ -19,31 +32,78 @@ impl FromStr for Position {
             "center" => Ok(Position::CenterAlign),
             "right" => Ok(Position::RightAlign),
             "prompt" => Ok(Position::Prompt),
+            "rprompt" => Ok(Position::RPrompt),
             _ => Err(()),
         }
     }
 }
 
-#[derive(Default)]
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Position::LeftAlign => "left",
+            Position::CenterAlign => "center",
+            Position::RightAlign => "right",
+            Position::Prompt => "prompt",
+            Position::RPrompt => "rprompt",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// what fit_prompt did to a section
+#[derive(Default, PartialEq, Clone, Copy)]
+pub enum Fitted {
+    #[default]
+    Full,
+    Shortened, // a shorter path or format was used
+    Hidden,
+}
+
+#[derive(Default, Clone)]
 pub struct SectionOptions {
     pub tilde: bool,
+    pub repo: bool, // show the path from the nearest project root
     pub not_zero: bool,
     pub not_empty: bool,
 }
 
+#[derive(Clone)]
 pub struct PromptSection {
+    pub name: String,
+    pub segment: String, // name of the segment that fills in the section
+    pub segment_config: toml::Table, // the section's config, for the segment's own keys
     pub text: String,
-    pub path: String, // text that will pass be used in path functions
+    pub path: path::SectionPath, // text that will pass be used in path functions
+    pub source_path: String,     // path before options change it, used to look at the filesystem
+    pub path_info: path::PathInfo,
+    pub shorten: path::Shorten,
+    pub root: String, // name of the project root when the repo option finds one
+    pub root_markers: Vec<String>, // files or directories that mark a project root
+    pub substitutions: Vec<path::Substitution>, // applied in order, before the tilde option
     pub icon: String,
+    pub readonly_icon: String, // shown by %l when the path isn't writable
+    pub missing_icon: String,  // shown by %m when the path doesn't exist
+    pub symlink_icon: String,  // shown by %s when the path goes through a symlink
     pub format: String,
+    // formats for each part of %p, where %p is the part
+    pub parent_format: String,
+    pub separator_format: String,
+    pub basename_format: String,
+    pub short_format: String, // tried before hiding the section, empty when unset
+    pub min_format: String,   // tried after short_format
     pub visible: bool,
-    // determines when section is hidden (lower is hidden first) (paths are shortened at 30,20,10)
+    // determines which sections are kept when the prompt is too long (lower is hidden first)
     pub priority: usize,
     pub foreground: String,
     pub background: String,
     pub position: Position,
     pub order: usize,
     pub options: SectionOptions,
+    pub when: Vec<WhenRule>, // applied in order after the section's data is collected
+    pub truncate: Option<usize>, // a tighter max for sized placeholders, set by fit_prompt
+    pub fitted: Fitted,
+    pub collect_time: Duration, // how long the segment took to fill in the section
 }
 
 impl PromptSection {
@@ -51,122 +111,328 @@ impl PromptSection {
         if self.options.not_zero && self.text == "0" {
             return false;
         }
-        if self.options.not_empty && self.text.is_empty() && self.path.is_empty() {
+        if self.options.not_empty
+            && self.text.is_empty()
+            && self.path.is_empty()
+            && self.root.is_empty()
+        {
             return false;
         }
         self.visible && !self.format.is_empty()
     }
 
-    fn apply_options(&mut self) {
-        if self.options.tilde {
-            let home = env::var("HOME").unwrap();
-            let tilde_path = self.path.strip_prefix(&home);
-            if tilde_path.is_some() {
-                self.path = format!("~{}", tilde_path.unwrap());
+    fn readonly_icon(&self) -> &str {
+        if self.path_info.writable {
+            ""
+        } else {
+            &self.readonly_icon
+        }
+    }
+
+    fn missing_icon(&self) -> &str {
+        if self.path_info.exists {
+            ""
+        } else {
+            &self.missing_icon
+        }
+    }
+
+    fn symlink_icon(&self) -> &str {
+        match self.path_info.symlink_target {
+            Some(_) => &self.symlink_icon,
+            None => "",
+        }
+    }
+
+    fn symlink_target(&self) -> &str {
+        self.path_info.symlink_target.as_deref().unwrap_or_default()
+    }
+
+    // whether a placeholder has something to show, for %?{<placeholder>}
+    fn has_value(&self, placeholder: &str) -> bool {
+        match placeholder {
+            "t" => !self.text.is_empty(),
+            "i" => !self.icon.is_empty(),
+            "p" => !self.path.is_empty(),
+            "R" => !self.root.is_empty(),
+            "l" => !self.readonly_icon().is_empty(),
+            "m" => !self.missing_icon().is_empty(),
+            "s" => !self.symlink_icon().is_empty(),
+            "S" => !self.symlink_target().is_empty(),
+            _ => false,
+        }
+    }
+
+    // every matching rule applies, so later rules win
+    fn apply_when(&mut self, context: &Context) {
+        for rule in &self.when {
+            if !rule.condition.matches(&self.text, context) {
+                continue;
             }
-        };
+            if let Some(foreground) = &rule.foreground {
+                self.foreground = foreground.clone();
+            }
+            if let Some(background) = &rule.background {
+                self.background = background.clone();
+            }
+            if let Some(icon) = &rule.icon {
+                self.icon = icon.clone();
+            }
+            if let Some(format) = &rule.format {
+                self.format = format.clone();
+            }
+        }
+    }
+
+    fn apply_segment_data(&mut self, data: SegmentData, shell: &shell::ShellInstance) {
+        if let Some(foreground) = data.foreground {
+            self.foreground = shell
+                .foreground
+                .get_escape(&foreground)
+                .unwrap_or(foreground);
+        }
+        if let Some(background) = data.background {
+            self.background = shell
+                .background
+                .get_escape(&background)
+                .unwrap_or(background);
+        }
+        if let Some(visible) = data.visible {
+            self.visible = visible;
+        }
+        if let Some(text) = data.text {
+            self.text = text;
+        }
+        if let Some(path) = data.path {
+            self.path = path::SectionPath::from(path.as_str());
+        }
+        if let Some(icon) = data.icon {
+            self.icon = icon;
+        }
+    }
+
+    fn apply_options(&mut self, home: &str) {
+        self.source_path = path::normalize(&self.path.to_string());
+        if !self.source_path.is_empty() {
+            self.path_info = path::PathInfo::new(&self.source_path);
+        }
+        if self.options.repo
+            && let Some(root) = path::find_root(&self.source_path, &self.root_markers)
+        {
+            // the path continues from the root, e.g. %R%p is 'repo/sub/dir'
+            self.path = path::SectionPath::from(&self.source_path[root.len()..]);
+            self.root = root.rsplit('/').next().unwrap_or_default().to_string();
+            return;
+        }
+        let tilde = self.options.tilde.then(path::Substitution::tilde);
+        let mut display_path = self.source_path.clone();
+        for substitution in self.substitutions.iter().chain(tilde.iter()) {
+            if let Some(path) = substitution.apply(&display_path, home) {
+                display_path = path;
+            }
+        }
+        self.path = path::SectionPath::from(display_path.as_str());
     }
 }
 
-#[derive(Default)]
+// a line of a multi-line prompt, unset values fall back to the prompt's values
+#[derive(Default, Clone)]
+pub struct PromptLine {
+    pub sections: Vec<String>, // section names placed on this line
+    pub section_pad: Option<usize>,
+    pub surround_pad: Option<usize>,
+    pub section_fill: Option<String>,
+    pub blank_fill: Option<String>,
+}
+
+// everything a prompt renders to, for the shell to pick from
+pub struct RenderedPrompt {
+    pub prompt: String,
+    pub rprompt: String,
+    pub transient: String,
+    pub continuation: String, // empty when not defined
+    pub select: String,
+    pub trace: String,
+    pub timings: RenderTimings,
+    pub errors: Vec<String>, // mistakes in the sections, like an unknown placeholder or type
+}
+
+// how long each stage of rendering took
+#[derive(Default, Clone)]
+pub struct RenderTimings {
+    pub collect: Vec<(String, String, Duration)>, // section name, segment, and its time
+    pub layout: Duration,                         // options, shortening, and fitting
+    pub format: Duration,                         // writing the sections out
+}
+
+#[derive(Default, Clone)]
 pub struct Prompt<'p> {
     pub sections: Vec<PromptSection>,
+    pub lines: Vec<PromptLine>,
+    pub transient: Option<PromptSection>, // replaces the prompt once a command is accepted
+    pub continuation: Option<PromptSection>, // PS2
+    pub select: Option<PromptSection>,    // PS3
+    pub trace: Option<PromptSection>,     // PS4
     pub newline: bool,
+    pub rprompt: bool, // move right aligned sections into RPROMPT
     pub section_pad: usize,
     pub surround_pad: usize,
     pub columns: usize,
+    pub home: String, // for the ~ option and substitutions
     pub foreground: String,
     pub background: String,
     pub section_fill: String,
     pub blank_fill: String,
     pub shell: shell::ShellInstance<'p>,
+    pub width: width::WidthOptions,
+    pub segments: Vec<Arc<dyn Segment>>, // later segments replace earlier ones with the same name
+    pub timings: RenderTimings,          // filled in while rendering
+    // shared with the copies made while rendering, which all format into the same list
+    pub errors: Arc<Mutex<Vec<String>>>,
+}
+
+fn report_error(errors: &Mutex<Vec<String>>, section: &PromptSection, error: &str) {
+    let error = format!("section '{}': {error}", section.name);
+    let mut errors = errors.lock().unwrap();
+    // sections are formatted more than once while they're fitted
+    if !errors.contains(&error) {
+        errors.push(error);
+    }
 }
 
-impl Prompt<'_> {
+impl<'p> Prompt<'p> {
     fn visible_sections_iter(&self) -> impl Iterator<Item = &PromptSection> {
         self.sections.iter().filter(|s| s.is_visible())
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // columns taken by the sections on the line, without the blank fill
     pub fn len(&self) -> usize {
         let mut len = 0;
         let mut left_aligned = 0; // number of sections aligned
         let mut center_aligned = 0;
         let mut right_aligned = 0;
-        for section in self
-            .sections
-            .iter()
-            .filter(|s| s.is_visible() && s.position != Position::Prompt)
-        {
-            let mut format_iter = section.format.chars().peekable();
-            let mut escaped = false;
-            let mut depth = 0;
-            while let Some(c) = format_iter.next() {
-                if escaped {
-                    match c {
-                        't' => {
-                            // text
-                            len += section.text.chars().count();
-                            escaped = false;
-                        }
-                        'i' => {
-                            // icon
-                            len += section.icon.chars().count();
-                            escaped = false;
-                        }
-                        'p' => {
-                            // path
-                            len += section.path.chars().count();
-                            escaped = false
-                        }
-                        'f' | 'b' => {
-                            if format_iter.peek().is_some_and(|c| *c == '{') {
-                                format_iter.next();
-                                depth += 1;
-                            } else {
-                                escaped = false;
-                            }
-                        }
-                        '}' => {
-                            // assuming a proper formatting string
-                            if depth > 0 {
-                                depth -= 1;
-                            } else {
-                                escaped = false
-                            }
-                        }
-                        'F' | 'B' => escaped = false,
-                        // could be an improper format, could be an escape code, we don't care because this should run as quickly as possible
-                        _ => (),
-                    }
-                } else {
-                    match c {
-                        '%' => escaped = true,
-                        _ => len += 1,
-                    }
-                }
-            }
+        for section in self.sections.iter().filter(|s| {
+            s.is_visible() && s.position != Position::Prompt && s.position != Position::RPrompt
+        }) {
+            len += self.section_width(section);
             match section.position {
                 Position::LeftAlign => left_aligned += 1,
                 Position::CenterAlign => center_aligned += 1,
                 Position::RightAlign => right_aligned += 1,
-                Position::Prompt => (),
+                Position::Prompt | Position::RPrompt => (),
             }
         }
+        let fill_width = self.width.width(&self.section_fill);
         if left_aligned > 0 {
-            len += self.section_fill.chars().count() * self.section_pad * (left_aligned - 1);
-            len += self.section_fill.chars().count() * self.surround_pad * 2;
+            len += fill_width * self.section_pad * (left_aligned - 1);
+            len += fill_width * self.surround_pad * 2;
         }
         if center_aligned > 0 {
-            len += self.section_fill.chars().count() * self.section_pad * (center_aligned - 1);
-            len += self.section_fill.chars().count() * self.surround_pad * 2;
+            len += fill_width * self.section_pad * (center_aligned - 1);
+            len += fill_width * self.surround_pad * 2;
         }
         if right_aligned > 0 {
-            len += self.section_fill.chars().count() * self.section_pad * (right_aligned - 1);
-            len += self.section_fill.chars().count() * self.surround_pad * 2;
+            len += fill_width * self.section_pad * (right_aligned - 1);
+            len += fill_width * self.surround_pad * 2;
         }
         len
     }
 
+    pub(crate) fn section_width(&self, section: &PromptSection) -> usize {
+        self.format_width(&section.format, section, None)
+    }
+
+    // path_part replaces %p while measuring the formats of the path's parts
+    fn format_width(
+        &self,
+        format: &str,
+        section: &PromptSection,
+        path_part: Option<&str>,
+    ) -> usize {
+        let mut len = 0;
+        let mut format_iter = format.chars().peekable();
+        let mut literal = String::new(); // measured at once so clusters aren't split
+        while let Some(c) = format_iter.next() {
+            if c != '%' {
+                literal.push(c);
+                continue;
+            }
+            let mut prefix = String::new();
+            while let Some(c) = format_iter.next_if(|c| *c == '-' || c.is_ascii_digit()) {
+                prefix.push(c);
+            }
+            match format_iter.next() {
+                Some(c @ ('t' | 'i' | 'R' | 'S')) => {
+                    len +=
+                        self.width
+                            .width(&self.sized_value(section, c, &prefix, &mut format_iter))
+                }
+                Some('p') => {
+                    len += match path_part {
+                        Some(part) => self.width.width(part),
+                        None => self
+                            .path_parts(section)
+                            .iter()
+                            .map(|(part_format, part)| {
+                                self.format_width(part_format, section, Some(part))
+                            })
+                            .sum(),
+                    }
+                }
+                Some('l') => len += self.width.width(section.readonly_icon()),
+                Some('m') => len += self.width.width(section.missing_icon()),
+                Some('s') => len += self.width.width(section.symlink_icon()),
+                Some('%') => literal.push('%'),
+                Some('{') => literal.push('{'),
+                // only the branch that is drawn counts
+                Some('?') if format_iter.peek().is_some_and(|c| *c == '{') => {
+                    let branch = self.conditional_branch(&mut format_iter, section);
+                    len += self.format_width(&branch, section, path_part);
+                }
+                // colors and escape codes take no columns
+                Some('f' | 'b' | 'e') if format_iter.peek().is_some_and(|c| *c == '{') => {
+                    self.get_arg(format_iter.by_ref());
+                }
+                _ => (),
+            }
+        }
+        len + self.width.width(&literal)
+    }
+
+    // the path's parts, each with the format it is drawn with
+    fn path_parts<'s>(&self, section: &'s PromptSection) -> Vec<(&'s str, &'s str)> {
+        let mut parts = Vec::new();
+        if section.path.is_empty() {
+            return parts;
+        }
+        let last_i = section.path.components.len() - 1;
+        for (component_i, component) in section.path.components.iter().enumerate() {
+            if component_i > 0 {
+                parts.push((section.separator_format.as_str(), "/"));
+            }
+            let part_format = if component_i == last_i {
+                &section.basename_format
+            } else {
+                &section.parent_format
+            };
+            parts.push((part_format.as_str(), component.as_str()));
+        }
+        parts
+    }
+
+    // repeats blank_fill over the columns, wide fills leave the remainder to spaces
+    fn fill_blank(&self, columns: usize) -> String {
+        let fill_width = self.width.width(&self.blank_fill);
+        if fill_width == 0 {
+            return " ".repeat(columns);
+        }
+        self.blank_fill.repeat(columns / fill_width) + &" ".repeat(columns % fill_width)
+    }
+
     fn get_foreground_color_escape(&self, color_escape: &str) -> String {
         let mut foreground_escape = String::new();
         let mut s_buf = [0; 4];
@@ -203,89 +469,139 @@ impl Prompt<'_> {
         if self.len() <= self.columns {
             return;
         }
-        let mut visibility_order_i: Vec<usize> = Vec::new();
-        let mut path_i: Vec<usize> = Vec::new(); // store an index for every path in the sections
-
-        // sort lowest visibility levels to the front
-        let mut vis_ord = self.sections.iter().enumerate().collect::<Vec<_>>();
-        vis_ord.sort_by(|a, b| {
-            (b.1.priority as isize - a.1.priority as isize).cmp(&(b.1.priority as isize))
-        });
-
-        // store indices in new vectors
-        for section_enum in vis_ord.iter() {
-            visibility_order_i.push(section_enum.0);
-            if !section_enum.1.path.is_empty() {
-                path_i.push(section_enum.0);
+        let fill_width = self.width.width(&self.section_fill);
+        let fitted_i: Vec<usize> = (0..self.sections.len())
+            .filter(|i| {
+                let section = &self.sections[*i];
+                section.is_visible()
+                    && section.position != Position::Prompt
+                    && section.position != Position::RPrompt
+            })
+            .collect();
+        let mut renderings: Vec<Vec<PromptSection>> = fitted_i
+            .iter()
+            .map(|i| self.renderings(&self.sections[*i]))
+            .collect();
+        let candidates: Vec<layout::Candidate> = fitted_i
+            .iter()
+            .zip(renderings.iter())
+            .map(|(i, section_renderings)| layout::Candidate {
+                group: match self.sections[*i].position {
+                    Position::LeftAlign => 0,
+                    Position::CenterAlign => 1,
+                    _ => 2,
+                },
+                priority: self.sections[*i].priority,
+                widths: section_renderings
+                    .iter()
+                    .map(|rendering| self.section_width(rendering))
+                    .collect(),
+            })
+            .collect();
+        let chosen = layout::solve(
+            &candidates,
+            fill_width * self.surround_pad * 2,
+            fill_width * self.section_pad,
+            self.columns,
+        );
+        for ((section_i, section_renderings), rendering) in
+            fitted_i.iter().zip(renderings.iter_mut()).zip(chosen)
+        {
+            match rendering {
+                Some(rendering) => {
+                    self.sections[*section_i] = section_renderings.swap_remove(rendering);
+                    if rendering > 0 {
+                        self.sections[*section_i].fitted = Fitted::Shortened;
+                    }
+                }
+                None => {
+                    self.sections[*section_i].visible = false;
+                    self.sections[*section_i].fitted = Fitted::Hidden;
+                }
             }
         }
+    }
 
-        let mut is_two_dir = false;
-        let mut is_one_dir = false;
-        let mut is_zero_dir = false;
-        for section_i in visibility_order_i {
-            if self.len() <= self.columns {
-                break;
+    // every way the section can be drawn, from the full section to the shortest
+    fn renderings(&self, section: &PromptSection) -> Vec<PromptSection> {
+        let mut renderings = self.shortenings(section);
+        // compact formats shorten again from the full path and text
+        for format in [&section.short_format, &section.min_format] {
+            if !format.is_empty() {
+                let mut compact = section.clone();
+                compact.format = format.to_string();
+                renderings.extend(self.shortenings(&compact));
             }
-            if !is_zero_dir && self.sections[section_i].priority < 10 {
-                is_zero_dir = true;
-                for p_i in &path_i {
-                    self.sections[*p_i].path = self.shorten_path(&self.sections[*p_i].path, 0);
-                }
-                if self.len() <= self.columns {
-                    break;
-                }
-            } else if !is_one_dir && self.sections[section_i].priority < 20 {
-                is_one_dir = true;
-                for p_i in &path_i {
-                    self.sections[*p_i].path = self.shorten_path(&self.sections[*p_i].path, 1);
-                }
-                if self.len() <= self.columns {
-                    break;
-                }
-            } else if !is_two_dir && self.sections[section_i].priority < 30 {
-                is_two_dir = true;
-                for p_i in &path_i {
-                    self.sections[*p_i].path = self.shorten_path(&self.sections[*p_i].path, 2);
-                }
-                if self.len() <= self.columns {
-                    break;
+        }
+        renderings
+    }
+
+    // the section in its own format, with the path shortened and then placeholders cut
+    fn shortenings(&self, section: &PromptSection) -> Vec<PromptSection> {
+        let mut renderings = vec![section.clone()];
+        if !section.path.is_empty() {
+            for long_levels in (0..=2).rev() {
+                let path = path::shorten_path(
+                    &section.path,
+                    &section.source_path,
+                    long_levels,
+                    section.shorten,
+                );
+                if renderings.last().is_some_and(|r| r.path != path) {
+                    let mut rendering = section.clone();
+                    rendering.path = path;
+                    renderings.push(rendering);
                 }
             }
-            self.sections[section_i].visible = false;
         }
+        // then placeholders with a max are cut, one column at a time so the layout can use
+        // every column it has
+        let base = renderings.last().unwrap().clone();
+        for limit in (1..self.section_width(&base)).rev() {
+            let mut rendering = base.clone();
+            rendering.truncate = Some(limit);
+            if self.section_width(&rendering) < self.section_width(renderings.last().unwrap()) {
+                renderings.push(rendering);
+            }
+        }
+        renderings
     }
 
-    fn shorten_path(&self, path: &str, long_levels: usize) -> String {
-        let mut new_path_vec: Vec<String> = path.split('/').map(|s| s.to_string()).collect();
-        let short_levels = if long_levels < new_path_vec.len() {
-            new_path_vec.len() - long_levels
-        } else {
-            new_path_vec.len()
-        };
-        (0..short_levels).for_each(|i| {
-            new_path_vec[i] = new_path_vec[i]
-                .chars()
-                .next()
-                .unwrap_or_default()
-                .to_string();
-        });
-        new_path_vec.join("/")
+    fn format_section(&self, section_i: usize) -> String {
+        let section = self.visible_sections_iter().nth(section_i).unwrap();
+        self.format_string(&section.format, section_i, None)
     }
 
-    fn format_section(&self, section_i: usize) -> String {
+    // path_part replaces %p while formatting the path's parts
+    fn format_string(&self, format: &str, section_i: usize, path_part: Option<&str>) -> String {
         let mut formatted = String::new();
-        let sections: Vec<&PromptSection> = self.visible_sections_iter().collect();
-        let section = sections[section_i];
-        let mut format_iter = section.format.chars().peekable();
+        let section = self.visible_sections_iter().nth(section_i).unwrap();
+        let mut format_iter = format.chars().peekable();
         let mut escaped = false;
+        let mut prefix = String::new(); // the width spec between '%' and the placeholder
         let mut s_buf = [0; 4];
         while let Some(c) = format_iter.next() {
             if escaped {
+                if c == '-' || c.is_ascii_digit() {
+                    prefix.push(c);
+                    continue;
+                }
                 match c {
-                    't' => formatted += &section.text,
-                    'i' => formatted += &section.icon,
-                    'p' => formatted += &section.path,
+                    't' | 'i' | 'R' | 'S' => {
+                        formatted += &self.sized_value(section, c, &prefix, &mut format_iter)
+                    }
+                    'p' => match path_part {
+                        Some(part) => formatted += part,
+                        None => {
+                            for (part_format, part) in self.path_parts(section) {
+                                formatted +=
+                                    &self.format_string(part_format, section_i, Some(part));
+                            }
+                        }
+                    },
+                    'l' => formatted += section.readonly_icon(),
+                    'm' => formatted += section.missing_icon(),
+                    's' => formatted += section.symlink_icon(),
                     'F' => formatted += &self.foreground,
                     'B' => formatted += &self.background,
                     'r' => formatted += self.shell.reset,
@@ -298,7 +614,7 @@ impl Prompt<'_> {
                             section_i,
                         ) {
                             Ok(s) => formatted += s,
-                            Err(e) => println!("{e}"), // TODO: log error
+                            Err(e) => self.format_error(section, e),
                         },
                         _ => formatted += &section.foreground,
                     },
@@ -309,13 +625,27 @@ impl Prompt<'_> {
                             section_i,
                         ) {
                             Ok(s) => formatted += s,
-                            Err(e) => println!("{e}"), // TODO: log error
+                            Err(e) => self.format_error(section, e),
                         },
                         _ => formatted += &section.background,
                     },
-                    _ => println!("ERROR: unrecognized char: '{c}'"), // TODO: log error
+                    'e' => match format_iter.peek() {
+                        Some('{') => {
+                            formatted += &self.shell.escape(&self.get_arg(format_iter.by_ref()))
+                        }
+                        _ => self.format_error(section, "%e needs an escape code argument"),
+                    },
+                    '?' => match format_iter.peek() {
+                        Some('{') => {
+                            let branch = self.conditional_branch(&mut format_iter, section);
+                            formatted += &self.format_string(&branch, section_i, path_part);
+                        }
+                        _ => self.format_error(section, "%? needs a condition argument"),
+                    },
+                    _ => self.format_error(section, &format!("unknown placeholder '%{c}'")),
                 }
                 escaped = false;
+                prefix.clear();
             } else {
                 match c {
                     '%' => escaped = true,
@@ -326,6 +656,12 @@ impl Prompt<'_> {
         formatted
     }
 
+    // a library can't print, so mistakes are kept for RenderedPrompt and the section's
+    // text goes on without the broken placeholder
+    fn format_error(&self, section: &PromptSection, error: &str) {
+        report_error(&self.errors, section, error);
+    }
+
     fn process_color_arg(
         &self,
         format_escape: char,
@@ -458,13 +794,57 @@ impl Prompt<'_> {
                     }
                 }
             } else {
-                // TODO: map colors to escape codes
-                return Err("Currently, color names are not mapped to their escape codes");
+                return match format_escape {
+                    'f' => self.shell.foreground.get_escape(arg),
+                    'b' => self.shell.background.get_escape(arg),
+                    _ => Err("Unrecognized format escape for color name"),
+                };
             }
         }
         Err("Unrecognized color arg")
     }
 
+    // %t, %i, %R, or %S, sized by the spec written around it
+    fn sized_value(
+        &self,
+        section: &PromptSection,
+        placeholder: char,
+        prefix: &str,
+        format_iter: &mut Peekable<impl Iterator<Item = char>>,
+    ) -> String {
+        let arg = match format_iter.peek() {
+            Some('{') => Some(self.get_arg(format_iter.by_ref())),
+            _ => None,
+        };
+        let value = match placeholder {
+            't' => &section.text,
+            'i' => &section.icon,
+            'R' => &section.root,
+            _ => section.symlink_target(),
+        };
+        let spec = width::WidthSpec::parse(prefix, arg.as_deref());
+        self.width.sized(value, &spec, section.truncate)
+    }
+
+    // reads {condition}{then} and an optional {else} after %?, returning the branch to draw
+    fn conditional_branch(
+        &self,
+        format_iter: &mut Peekable<impl Iterator<Item = char>>,
+        section: &PromptSection,
+    ) -> String {
+        let condition = self.get_arg(format_iter.by_ref());
+        let branch = |format_iter: &mut Peekable<_>| match format_iter.peek() {
+            Some('{') => self.get_arg(format_iter.by_ref()),
+            _ => String::new(),
+        };
+        let then_branch = branch(format_iter);
+        let else_branch = branch(format_iter);
+        match section.has_value(&condition) {
+            true => then_branch,
+            false => else_branch,
+        }
+    }
+
     fn get_arg(&self, char_iter: &mut impl Iterator<Item = char>) -> String {
         let mut arg = String::new();
         let mut depth = 0;
@@ -486,28 +866,254 @@ impl Prompt<'_> {
         arg
     }
 
+    // only zsh and fish can draw a right prompt, bash keeps those sections in the main line
+    fn resolve_rprompt(&mut self) {
+        let has_rprompt = self.shell.kind != shell::Shell::Bash;
+        for section in self.sections.iter_mut() {
+            match section.position {
+                Position::RightAlign if has_rprompt && self.rprompt => {
+                    section.position = Position::RPrompt
+                }
+                Position::RPrompt if !has_rprompt => section.position = Position::RightAlign,
+                _ => (),
+            }
+        }
+    }
+
+    pub fn register_segment(&mut self, segment: impl Segment + 'static) {
+        self.segments.push(Arc::new(segment));
+    }
+
+    // fills in every section from its segment
+    pub(crate) fn collect(&mut self, context: &Context) {
+        let segments = self.segments.clone();
+        let sections = self.sections.iter_mut().chain(
+            [
+                &mut self.transient,
+                &mut self.continuation,
+                &mut self.select,
+                &mut self.trace,
+            ]
+            .into_iter()
+            .flatten(),
+        );
+        for section in sections {
+            // a mistake in the config hides the section instead of the whole prompt
+            let Some(segment) = segments
+                .iter()
+                .rev()
+                .find(|segment| segment.name() == section.segment)
+            else {
+                let error = format!("unknown type '{}'", section.segment);
+                report_error(&self.errors, section, &error);
+                section.visible = false;
+                continue;
+            };
+            if let Some(unknown) = section.segment_config.keys().find(|key| {
+                !SECTION_KEYS.contains(&key.as_str()) && !segment.keys().contains(&key.as_str())
+            }) {
+                report_error(&self.errors, section, &format!("unknown key '{unknown}'"));
+                section.visible = false;
+                continue;
+            }
+            let start = Instant::now();
+            let data =
+                segment::collect_section(segment, &section.name, &section.segment_config, context);
+            section.collect_time = start.elapsed();
+            self.timings.collect.push((
+                section.name.clone(),
+                section.segment.clone(),
+                section.collect_time,
+            ));
+            section.apply_segment_data(
+                segment::configured_data(&section.segment_config, context),
+                &self.shell,
+            );
+            section.apply_segment_data(data, &self.shell);
+            section.apply_when(context);
+        }
+    }
+
+    // a copy with every section's data collected for the context, ready to lay out
+    pub(crate) fn collected(&self, context: &Context) -> Prompt<'p> {
+        let mut prompt = self.clone();
+        prompt.columns = context.columns;
+        prompt.home = context.home().to_string();
+        prompt.errors = Default::default();
+        prompt.collect(context);
+        prompt
+    }
+
+    pub fn render(&self, context: &Context) -> RenderedPrompt {
+        let prompt = self.collected(context);
+        let mut timings = prompt.timings.clone();
+        let mut text = |render: fn(&mut Prompt<'p>) -> String| {
+            let mut output = prompt.clone();
+            let text = render(&mut output);
+            timings.layout += output.timings.layout;
+            timings.format += output.timings.format;
+            text
+        };
+        RenderedPrompt {
+            prompt: text(Prompt::term_text),
+            rprompt: text(Prompt::rprompt_text),
+            transient: text(Prompt::transient_text),
+            continuation: text(Prompt::continuation_text),
+            select: text(Prompt::select_text),
+            trace: text(Prompt::trace_text),
+            timings,
+            errors: prompt.errors.lock().unwrap().clone(),
+        }
+    }
+
+    pub fn rprompt_text(&mut self) -> String {
+        self.resolve_rprompt();
+        self.sections.retain(|s| s.position == Position::RPrompt);
+        // the shell handles alignment, so lay the sections out as a single left aligned group
+        self.sections
+            .iter_mut()
+            .for_each(|s| s.position = Position::LeftAlign);
+        self.newline = false;
+        self.lines.clear();
+        self.term_text()
+    }
+
+    pub fn transient_text(&mut self) -> String {
+        match self.transient.take() {
+            Some(transient) => self.section_text(transient),
+            // without a transient definition, keep only the prompt sections
+            None => {
+                self.sections.retain(|s| s.position == Position::Prompt);
+                self.newline = false;
+                self.lines.clear();
+                self.term_text()
+            }
+        }
+    }
+
+    // the secondary prompts are empty when they aren't defined, so the shell keeps its own
+    pub fn continuation_text(&mut self) -> String {
+        self.continuation
+            .take()
+            .map(|continuation| self.section_text(continuation))
+            .unwrap_or_default()
+    }
+
+    pub fn select_text(&mut self) -> String {
+        self.select
+            .take()
+            .map(|select| self.section_text(select))
+            .unwrap_or_default()
+    }
+
+    pub fn trace_text(&mut self) -> String {
+        self.trace
+            .take()
+            .map(|trace| self.section_text(trace))
+            .unwrap_or_default()
+    }
+
+    // renders a single section on its own, without any fill
+    fn section_text(&mut self, mut section: PromptSection) -> String {
+        section.position = Position::Prompt;
+        self.sections = vec![section];
+        self.newline = false;
+        self.lines.clear();
+        self.term_text()
+    }
+
     pub fn term_text(&mut self) -> String {
         let mut prompt = String::new();
-        let mut previous_position: Option<Position> = None;
+        if self.newline {
+            prompt += "\n";
+        }
+
+        self.resolve_rprompt();
+        self.sections.retain(|s| s.position != Position::RPrompt);
 
-        self.sections.iter_mut().for_each(|s| s.apply_options());
+        if self.lines.is_empty() {
+            return prompt + &self.line_text();
+        }
+        let lines = std::mem::take(&mut self.lines);
+        let last_line_i = lines.len() - 1;
+        let mut line_texts = Vec::new();
+        let mut laid_out = Vec::new();
+        for (line_i, line) in lines.iter().enumerate() {
+            let mut line_prompt = self.line_prompt(line, line_i == last_line_i);
+            line_texts.push(line_prompt.line_text());
+            self.timings.layout += line_prompt.timings.layout;
+            self.timings.format += line_prompt.timings.format;
+            laid_out.append(&mut line_prompt.sections);
+        }
+        // keep the sections as they were laid out, for explain
+        self.sections = laid_out;
+        prompt + &line_texts.join("\n")
+    }
+
+    // moves the line's sections into a new prompt, the last line takes every remaining section
+    fn line_prompt(&mut self, line: &PromptLine, is_last: bool) -> Prompt<'p> {
+        let sections = self
+            .sections
+            .extract_if(.., |s| is_last || line.sections.contains(&s.name))
+            .collect();
+        Prompt {
+            sections,
+            lines: Vec::new(),
+            transient: None,
+            continuation: None,
+            select: None,
+            trace: None,
+            newline: false,
+            rprompt: self.rprompt,
+            section_pad: line.section_pad.unwrap_or(self.section_pad),
+            surround_pad: line.surround_pad.unwrap_or(self.surround_pad),
+            columns: self.columns,
+            home: self.home.clone(),
+            foreground: self.foreground.clone(),
+            background: self.background.clone(),
+            section_fill: line
+                .section_fill
+                .clone()
+                .unwrap_or_else(|| self.section_fill.clone()),
+            blank_fill: line
+                .blank_fill
+                .clone()
+                .unwrap_or_else(|| self.blank_fill.clone()),
+            shell: self.shell.clone(),
+            width: self.width,
+            segments: self.segments.clone(),
+            timings: Default::default(),
+            errors: self.errors.clone(),
+        }
+    }
+
+    fn line_text(&mut self) -> String {
+        let layout_start = Instant::now();
+        let home = self.home.clone();
+        self.sections
+            .iter_mut()
+            .for_each(|s| s.apply_options(&home));
 
         self.sections.sort_by_key(|s| s.position as isize);
         self.fit_prompt();
+        self.timings.layout += layout_start.elapsed();
+
+        let format_start = Instant::now();
+        let prompt = self.aligned_text();
+        self.timings.format += format_start.elapsed();
+        prompt
+    }
+
+    // the line's visible sections, with blank fill and padding between them
+    fn aligned_text(&self) -> String {
+        let mut prompt = String::new();
+        let mut previous_position: Option<Position> = None;
 
         let len = self.len();
-        let line_columns = if self.columns > len {
-            self.columns - len
-        } else {
-            0
-        };
+        let line_columns = self.columns.saturating_sub(len);
         let left_columns = (line_columns / 2) + (line_columns % 2);
         let right_columns = line_columns / 2;
 
-        if self.newline {
-            println!();
-        }
-
         let mut visible_section_iter = self.visible_sections_iter().enumerate().peekable();
         while let Some((section_i, section)) = visible_section_iter.next() {
             // alignment code
@@ -515,23 +1121,28 @@ impl Prompt<'_> {
                 None | Some(Position::LeftAlign) => match section.position {
                     Position::LeftAlign => (),
                     Position::CenterAlign => {
-                        prompt += &self.blank_fill.repeat(left_columns);
+                        prompt += &self.fill_blank(left_columns);
                     }
                     Position::RightAlign => {
-                        prompt += &self.blank_fill.repeat(line_columns);
+                        prompt += &self.fill_blank(line_columns);
                     }
+                    // a line of only prompt sections has nothing to fill
+                    Position::Prompt if previous_position.is_none() => (),
                     Position::Prompt => {
-                        prompt += &self.blank_fill.repeat(line_columns);
+                        prompt += &self.fill_blank(line_columns);
                     }
+                    Position::RPrompt => (),
                 },
                 Some(Position::CenterAlign) => match section.position {
                     Position::LeftAlign => (),
                     Position::CenterAlign => (),
-                    Position::RightAlign => prompt += &self.blank_fill.repeat(right_columns),
-                    Position::Prompt => prompt += &self.blank_fill.repeat(right_columns),
+                    Position::RightAlign => prompt += &self.fill_blank(right_columns),
+                    Position::Prompt => prompt += &self.fill_blank(right_columns),
+                    Position::RPrompt => (),
                 },
                 Some(Position::RightAlign) => (),
                 Some(Position::Prompt) => (),
+                Some(Position::RPrompt) => (),
             }
 
             if section.position != Position::Prompt {
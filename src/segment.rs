@@ -0,0 +1,325 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, mpsc},
+    thread,
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use serde_json::{Map, Value, json};
+use toml::Table;
+
+#[cfg(feature = "wasm")]
+use crate::wasm::WasmSegment;
+use crate::{cache, context::Context, plugin::PluginSegment};
+
+// where a section's text, path, and icon come from, selected with `type = "<name>"`
+pub trait Segment: Send + Sync {
+    fn name(&self) -> &str;
+    // keys the segment reads from its section, on top of the keys every section has
+    fn keys(&self) -> &[&str];
+    fn collect(&self, config: &Table, context: &Context) -> SegmentData;
+    // segments that stop by themselves at the section's timeout, like plugins killing their
+    // program, are collected directly instead of on a thread that would be left running
+    fn stops_at_timeout(&self) -> bool {
+        false
+    }
+}
+
+// values left as None keep what the section defines
+#[derive(Default, Clone)]
+pub struct SegmentData {
+    pub text: Option<String>,
+    pub path: Option<String>,
+    pub icon: Option<String>,
+    pub foreground: Option<String>, // color name or escape code
+    pub background: Option<String>,
+    pub visible: Option<bool>,
+}
+
+impl SegmentData {
+    pub(crate) fn hidden() -> Self {
+        SegmentData {
+            visible: Some(false),
+            ..Default::default()
+        }
+    }
+
+    // the JSON that plugins answer with, which is also how data is cached
+    pub fn from_json(value: &Value) -> Self {
+        let string = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        SegmentData {
+            text: string("text"),
+            path: string("path"),
+            icon: string("icon"),
+            foreground: string("foreground"),
+            background: string("background"),
+            visible: value.get("visible").and_then(Value::as_bool),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut object = Map::new();
+        let strings = [
+            ("text", &self.text),
+            ("path", &self.path),
+            ("icon", &self.icon),
+            ("foreground", &self.foreground),
+            ("background", &self.background),
+        ];
+        for (key, value) in strings {
+            if let Some(value) = value {
+                object.insert(key.to_string(), json!(value));
+            }
+        }
+        if let Some(visible) = self.visible {
+            object.insert("visible".to_string(), json!(visible));
+        }
+        Value::Object(object)
+    }
+}
+
+// collects a section's data, honoring its timeout (milliseconds) and cache (seconds) keys
+pub(crate) fn collect_section(
+    segment: &Arc<dyn Segment>,
+    section_name: &str,
+    config: &Table,
+    context: &Context,
+) -> SegmentData {
+    let cache_age = config.get("cache").map(|cache| {
+        Duration::from_secs(
+            cache
+                .as_integer()
+                .and_then(|cache| u64::try_from(cache).ok())
+                .expect("sections.cache must be a positive integer"),
+        )
+    });
+    let timeout = section_timeout(config);
+    let cache_path = cache_age.map(|_| cache::entry_path(section_name, segment.name(), context));
+    if let Some(cache_path) = &cache_path
+        && let Some(data) = cache::read(cache_path, cache_age)
+    {
+        return data;
+    }
+
+    // an outdated cache entry is better than nothing
+    let fallback = |cache_path: Option<PathBuf>| {
+        cache_path
+            .and_then(|cache_path| cache::read(&cache_path, None))
+            .unwrap_or_else(SegmentData::hidden)
+    };
+    let data = match timeout {
+        None => segment.collect(config, context),
+        Some(timeout) if segment.stops_at_timeout() => {
+            let started = Instant::now();
+            let data = segment.collect(config, context);
+            if started.elapsed() >= timeout {
+                return fallback(cache_path);
+            }
+            data
+        }
+        Some(timeout) => {
+            let (sender, receiver) = mpsc::channel();
+            let (thread_segment, thread_config, thread_context) =
+                (segment.clone(), config.clone(), context.clone());
+            thread::spawn(move || {
+                let _ = sender.send(thread_segment.collect(&thread_config, &thread_context));
+            });
+            match receiver.recv_timeout(timeout) {
+                Ok(data) => data,
+                Err(_) => return fallback(cache_path),
+            }
+        }
+    };
+    if let Some(cache_path) = &cache_path {
+        cache::write(cache_path, &data);
+    }
+    data
+}
+
+pub(crate) fn section_timeout(config: &Table) -> Option<Duration> {
+    config.get("timeout").map(|timeout| {
+        Duration::from_millis(
+            timeout
+                .as_integer()
+                .and_then(|timeout| u64::try_from(timeout).ok())
+                .expect("sections.timeout must be a positive integer"),
+        )
+    })
+}
+
+pub fn builtin_segments() -> Vec<Arc<dyn Segment>> {
+    vec![
+        Arc::new(EnvSegment),
+        Arc::new(PathSegment),
+        Arc::new(GitSegment),
+        Arc::new(TimeSegment),
+        Arc::new(PluginSegment),
+        #[cfg(feature = "wasm")]
+        Arc::new(WasmSegment),
+    ]
+}
+
+fn config_str<'c>(config: &'c Table, key: &str) -> Option<&'c str> {
+    config.get(key).map(|value| {
+        value
+            .as_str()
+            .unwrap_or_else(|| panic!("sections.{key} must be a string"))
+    })
+}
+
+// text, path, and icon as configured, which every section gets from configured_data
+pub struct EnvSegment;
+
+impl Segment for EnvSegment {
+    fn name(&self) -> &str {
+        "env"
+    }
+
+    fn keys(&self) -> &[&str] {
+        &[]
+    }
+
+    fn collect(&self, _config: &Table, _context: &Context) -> SegmentData {
+        SegmentData::default()
+    }
+}
+
+// the section's own text, path, and icon with their environment variables expanded, see
+// Context::interpolate. Segments apply their data over these.
+pub(crate) fn configured_data(config: &Table, context: &Context) -> SegmentData {
+    // unset variables are empty, which not_empty can hide
+    let expand = |value: &str| context.interpolate(value);
+    SegmentData {
+        text: config_str(config, "text").map(expand),
+        path: config_str(config, "path").map(expand),
+        icon: config_str(config, "icon").map(expand),
+        ..Default::default()
+    }
+}
+
+// the current directory as the path
+pub struct PathSegment;
+
+impl Segment for PathSegment {
+    fn name(&self) -> &str {
+        "path"
+    }
+
+    fn keys(&self) -> &[&str] {
+        &[]
+    }
+
+    fn collect(&self, _config: &Table, context: &Context) -> SegmentData {
+        SegmentData {
+            path: Some(context.cwd.to_string_lossy().to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+// the checked out branch (or commit when detached) as the text, read straight from .git
+pub struct GitSegment;
+
+impl Segment for GitSegment {
+    fn name(&self) -> &str {
+        "git"
+    }
+
+    fn keys(&self) -> &[&str] {
+        &[]
+    }
+
+    fn collect(&self, _config: &Table, context: &Context) -> SegmentData {
+        SegmentData {
+            text: Some(git_head(&context.cwd).unwrap_or_default()),
+            ..Default::default()
+        }
+    }
+}
+
+fn git_head(dir: &Path) -> Option<String> {
+    let dot_git = dir
+        .ancestors()
+        .map(|dir| dir.join(".git"))
+        .find(|dot_git| dot_git.exists())?;
+    // worktrees and submodules have a file pointing to the git directory
+    let git_dir = if dot_git.is_file() {
+        let contents = fs::read_to_string(&dot_git).ok()?;
+        let git_dir = PathBuf::from(contents.strip_prefix("gitdir:")?.trim());
+        dot_git.parent()?.join(git_dir)
+    } else {
+        dot_git
+    };
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: ") {
+        Some(reference) => Some(
+            reference
+                .strip_prefix("refs/heads/")
+                .unwrap_or(reference)
+                .to_string(),
+        ),
+        None => Some(head.chars().take(7).collect()),
+    }
+}
+
+// the local time as the text, formatted with time_format (strftime, default "%H:%M:%S")
+pub struct TimeSegment;
+
+impl Segment for TimeSegment {
+    fn name(&self) -> &str {
+        "time"
+    }
+
+    fn keys(&self) -> &[&str] {
+        &["time_format"]
+    }
+
+    fn collect(&self, config: &Table, context: &Context) -> SegmentData {
+        let seconds = context
+            .time
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        SegmentData {
+            text: Some(format_time(
+                seconds,
+                config_str(config, "time_format").unwrap_or("%H:%M:%S"),
+            )),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(unix)]
+fn format_time(seconds: u64, time_format: &str) -> String {
+    use std::ffi::{CStr, CString};
+
+    let Ok(c_format) = CString::new(time_format) else {
+        return String::new();
+    };
+    let time = seconds as libc::time_t;
+    let mut buf = [0 as libc::c_char; 128];
+    // localtime_r and strftime only write into the buffers given to them
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return String::new();
+        }
+        if libc::strftime(buf.as_mut_ptr(), buf.len(), c_format.as_ptr(), &tm) == 0 {
+            return String::new();
+        }
+        CStr::from_ptr(buf.as_ptr()).to_string_lossy().to_string()
+    }
+}
+
+// without libc the time is UTC, and only %H, %M, and %S are understood
+#[cfg(not(unix))]
+fn format_time(seconds: u64, time_format: &str) -> String {
+    let day_seconds = seconds % 86400;
+    time_format
+        .replace("%H", &format!("{:02}", day_seconds / 3600))
+        .replace("%M", &format!("{:02}", day_seconds / 60 % 60))
+        .replace("%S", &format!("{:02}", day_seconds % 60))
+}
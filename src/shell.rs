@@ -1,8 +1,11 @@
 use std::str::FromStr;
 
+#[derive(Default, PartialEq, Clone, Copy)]
 pub enum Shell {
+    #[default]
     Bash,
     Zsh,
+    Fish,
 }
 
 impl FromStr for Shell {
@@ -11,12 +14,13 @@ impl FromStr for Shell {
         match s.to_lowercase().as_str() {
             "bash" => Ok(Shell::Bash),
             "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
             _ => Err(()),
         }
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct ColorEscapes<'c> {
     pub black: &'c str,
     pub red: &'c str,
@@ -44,17 +48,55 @@ impl ColorEscapes<'_> {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct ShellInstance<'s> {
+    pub kind: Shell,
     pub reset: &'s str,
     pub foreground: ColorEscapes<'s>,
     pub background: ColorEscapes<'s>,
 }
 
 impl ShellInstance<'_> {
+    // wraps an SGR code (1 is bold, 2 is dim, ...) so the shell knows it takes no columns:
+    // \001 and \002 are what bash's \[ and \] become, fish measures the prompt itself
+    pub fn escape(&self, code: &str) -> String {
+        match self.kind {
+            Shell::Bash => format!("\x01\x1B[{code}m\x02"),
+            Shell::Fish => format!("\x1B[{code}m"),
+            Shell::Zsh => format!("%{{\x1B[{code}m%}}"),
+        }
+    }
+
     pub fn new(shell: Shell) -> Self {
         match shell {
+            // bash skips what's between \001 and \002 when it measures the prompt
             Shell::Bash => ShellInstance {
+                kind: shell,
+                reset: "\x01\x1B[0m\x02",
+                foreground: ColorEscapes {
+                    black: "\x01\x1B[30m\x02",
+                    red: "\x01\x1B[31m\x02",
+                    green: "\x01\x1B[32m\x02",
+                    yellow: "\x01\x1B[33m\x02",
+                    blue: "\x01\x1B[34m\x02",
+                    magenta: "\x01\x1B[35m\x02",
+                    cyan: "\x01\x1B[36m\x02",
+                    white: "\x01\x1B[37m\x02",
+                },
+                background: ColorEscapes {
+                    black: "\x01\x1B[40m\x02",
+                    red: "\x01\x1B[41m\x02",
+                    green: "\x01\x1B[42m\x02",
+                    yellow: "\x01\x1B[43m\x02",
+                    blue: "\x01\x1B[44m\x02",
+                    magenta: "\x01\x1B[45m\x02",
+                    cyan: "\x01\x1B[46m\x02",
+                    white: "\x01\x1B[47m\x02",
+                },
+            },
+            // fish measures the prompt itself, so escapes need no markers
+            Shell::Fish => ShellInstance {
+                kind: shell,
                 reset: "\x1B[0m",
                 foreground: ColorEscapes {
                     black: "\x1B[30m",
@@ -78,6 +120,7 @@ impl ShellInstance<'_> {
                 },
             },
             Shell::Zsh => ShellInstance {
+                kind: shell,
                 reset: "%{\x1B[0m%}",
                 foreground: ColorEscapes {
                     black: "%{\x1B[30m%}",
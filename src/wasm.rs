@@ -0,0 +1,195 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use toml::Table;
+use wasmi::{
+    AsContext, Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder,
+};
+
+use crate::{
+    context::Context,
+    plugin::{env_names, expand_home, request_json},
+    segment::{Segment, SegmentData},
+};
+
+// enough for any reasonable segment, while a plugin stuck in a loop still ends
+const FUEL: u64 = 100_000_000;
+// memory can't grow past this, so a plugin can't make unprompt allocate without bound
+const MEMORY_LIMIT: usize = 16 << 20;
+
+// loads a WebAssembly module and calls it in process. It gets the same JSON request and
+// answers with the same JSON as an executable plugin, but can only reach the outside
+// through the host functions below.
+//
+// The module exports `memory`, `alloc(len) -> ptr` for the request, and
+// `collect(ptr, len) -> i64` returning the response's pointer in the high 32 bits
+// and its length in the low 32 bits. It can import from "unprompt":
+// - env_get(name_ptr, name_len, out_ptr, out_len) -> i32: reads an environment variable
+//   listed in the section's env key
+// - read_file(path_ptr, path_len, out_ptr, out_len) -> i32: reads a file inside the
+//   current directory
+// Both copy as much as fits in out_len and return the full length, or -1 if it isn't there.
+pub struct WasmSegment;
+
+impl Segment for WasmSegment {
+    fn name(&self) -> &str {
+        "wasm"
+    }
+
+    fn keys(&self) -> &[&str] {
+        &["wasm", "env"]
+    }
+
+    fn collect(&self, config: &Table, context: &Context) -> SegmentData {
+        run_plugin(config, context).unwrap_or_else(SegmentData::hidden)
+    }
+}
+
+struct HostState {
+    cwd: PathBuf,
+    env: HashMap<String, String>, // only the variables listed in the section's env key
+    limits: StoreLimits,
+}
+
+fn run_plugin(config: &Table, context: &Context) -> Option<SegmentData> {
+    let wasm_path = expand_home(
+        config
+            .get("wasm")
+            .expect("wasm sections need a 'wasm' file")
+            .as_str()
+            .expect("sections.wasm must be a string"),
+        context,
+    );
+    let wasm = fs::read(wasm_path).ok()?;
+
+    let mut engine_config = Config::default();
+    engine_config.consume_fuel(true);
+    let engine = Engine::new(&engine_config);
+    let module = Module::new(&engine, &wasm).ok()?;
+    let state = HostState {
+        cwd: context.cwd.clone(),
+        env: env_names(config)
+            .into_iter()
+            .filter_map(|var| {
+                context
+                    .var(&var)
+                    .map(|value| (var.clone(), value.to_string()))
+            })
+            .collect(),
+        limits: StoreLimitsBuilder::new()
+            .memory_size(MEMORY_LIMIT)
+            .memories(1)
+            .build(),
+    };
+    let mut store = Store::new(&engine, state);
+    store.limiter(|state| &mut state.limits);
+    store.set_fuel(FUEL).ok()?;
+
+    let mut linker = Linker::new(&engine);
+    linker
+        .func_wrap(
+            "unprompt",
+            "env_get",
+            |mut caller: Caller<'_, HostState>,
+             name_ptr: u32,
+             name_len: u32,
+             out_ptr: u32,
+             out_len: u32| {
+                let value = read_string(&caller, name_ptr, name_len)
+                    .and_then(|name| caller.data().env.get(&name).cloned());
+                match value {
+                    Some(value) => write_bytes(&mut caller, value.as_bytes(), out_ptr, out_len),
+                    None => -1,
+                }
+            },
+        )
+        .ok()?;
+    linker
+        .func_wrap(
+            "unprompt",
+            "read_file",
+            |mut caller: Caller<'_, HostState>,
+             path_ptr: u32,
+             path_len: u32,
+             out_ptr: u32,
+             out_len: u32| {
+                let contents = read_string(&caller, path_ptr, path_len)
+                    .and_then(|path| read_under(&caller.data().cwd, &path));
+                match contents {
+                    Some(contents) => write_bytes(&mut caller, &contents, out_ptr, out_len),
+                    None => -1,
+                }
+            },
+        )
+        .ok()?;
+
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .ok()?
+        .start(&mut store)
+        .ok()?;
+    let memory = instance.get_memory(&store, "memory")?;
+    let alloc = instance.get_typed_func::<u32, u32>(&store, "alloc").ok()?;
+    let collect = instance
+        .get_typed_func::<(u32, u32), u64>(&store, "collect")
+        .ok()?;
+
+    let request = request_json(config, context).to_string();
+    let request_len = u32::try_from(request.len()).ok()?;
+    let request_ptr = alloc.call(&mut store, request_len).ok()?;
+    memory
+        .write(&mut store, request_ptr as usize, request.as_bytes())
+        .ok()?;
+    let packed = collect.call(&mut store, (request_ptr, request_len)).ok()?;
+    let response = read_bytes(
+        memory,
+        &store,
+        (packed >> 32) as u32,
+        (packed & 0xFFFF_FFFF) as u32,
+    )?;
+    Some(SegmentData::from_json(
+        &serde_json::from_slice(&response).ok()?,
+    ))
+}
+
+// a path that leaves the directory, even through a symlink, isn't readable
+fn read_under(cwd: &Path, path: &str) -> Option<Vec<u8>> {
+    let root = cwd.canonicalize().ok()?;
+    let file = root.join(path).canonicalize().ok()?;
+    if !file.starts_with(&root) {
+        return None;
+    }
+    fs::read(file).ok()
+}
+
+// the range is checked against the plugin's memory before anything is allocated for it
+fn read_bytes(
+    memory: Memory,
+    store: impl AsContext<Data = HostState>,
+    ptr: u32,
+    len: u32,
+) -> Option<Vec<u8>> {
+    let start = ptr as usize;
+    let end = start.checked_add(len as usize)?;
+    memory.data(&store).get(start..end).map(<[u8]>::to_vec)
+}
+
+fn read_string(caller: &Caller<'_, HostState>, ptr: u32, len: u32) -> Option<String> {
+    let memory = caller.get_export("memory").and_then(Extern::into_memory)?;
+    String::from_utf8(read_bytes(memory, caller, ptr, len)?).ok()
+}
+
+fn write_bytes(caller: &mut Caller<'_, HostState>, bytes: &[u8], ptr: u32, len: u32) -> i32 {
+    let Some(memory) = caller.get_export("memory").and_then(Extern::into_memory) else {
+        return -1;
+    };
+    let copied = &bytes[..bytes.len().min(len as usize)];
+    if memory.write(caller, ptr as usize, copied).is_err() {
+        return -1;
+    }
+    i32::try_from(bytes.len()).unwrap_or(i32::MAX)
+}
@@ -0,0 +1,106 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// how many terminal columns a string takes, which depends on the terminal's font
+#[derive(Default, Clone, Copy)]
+pub struct WidthOptions {
+    pub ambiguous_wide: bool, // East Asian ambiguous characters take two columns
+    pub nerd_font_wide: bool, // Nerd Font icons take two columns
+}
+
+// sizing for a placeholder, from %-10t or %t{max=20,ellipsis=…}
+#[derive(Clone)]
+pub struct WidthSpec {
+    pub min: usize,         // padded with spaces up to this width
+    pub pad_left: bool,     // %10t pads before the value, %-10t and min= pad after it
+    pub max: Option<usize>, // cut down to this width, ending with the ellipsis
+    pub ellipsis: String,
+}
+
+impl Default for WidthSpec {
+    fn default() -> Self {
+        WidthSpec {
+            min: 0,
+            pad_left: false,
+            max: None,
+            ellipsis: "…".to_string(),
+        }
+    }
+}
+
+impl WidthSpec {
+    // prefix is what's between '%' and the placeholder, arg is what's in its braces
+    pub fn parse(prefix: &str, arg: Option<&str>) -> Self {
+        let mut spec = WidthSpec::default();
+        if let Some(min) = prefix.strip_prefix('-') {
+            spec.min = min.parse().unwrap_or_default();
+        } else if !prefix.is_empty() {
+            spec.min = prefix.parse().unwrap_or_default();
+            spec.pad_left = true;
+        }
+        for setting in arg.unwrap_or_default().split(',') {
+            match setting.split_once('=') {
+                Some(("max", max)) => spec.max = max.trim().parse().ok(),
+                Some(("min", min)) => spec.min = min.trim().parse().unwrap_or_default(),
+                Some(("ellipsis", ellipsis)) => spec.ellipsis = ellipsis.to_string(),
+                _ => (),
+            }
+        }
+        spec
+    }
+}
+
+impl WidthOptions {
+    // limit is a tighter max from fitting the prompt, it only shortens values that have a max
+    pub fn sized(&self, value: &str, spec: &WidthSpec, limit: Option<usize>) -> String {
+        let mut sized = value.to_string();
+        let max = spec
+            .max
+            .map(|max| limit.map_or(max, |limit| limit.min(max)));
+        if let Some(max) = max
+            && self.width(value) > max
+        {
+            let ellipsis = match self.width(&spec.ellipsis) <= max {
+                true => spec.ellipsis.as_str(),
+                false => "",
+            };
+            let room = max - self.width(ellipsis);
+            let mut kept_width = 0;
+            sized = value
+                .graphemes(true)
+                .take_while(|grapheme| {
+                    kept_width += self.grapheme_width(grapheme);
+                    kept_width <= room
+                })
+                .collect::<String>()
+                + ellipsis;
+        }
+        let padding = " ".repeat(spec.min.saturating_sub(self.width(&sized)));
+        match spec.pad_left {
+            true => padding + &sized,
+            false => sized + &padding,
+        }
+    }
+
+    pub fn width(&self, s: &str) -> usize {
+        s.graphemes(true).map(|g| self.grapheme_width(g)).sum()
+    }
+
+    fn grapheme_width(&self, grapheme: &str) -> usize {
+        // Nerd Font icons live in the private use areas
+        if grapheme.chars().next().is_some_and(is_private_use) {
+            return if self.nerd_font_wide { 2 } else { 1 };
+        }
+        let width = if self.ambiguous_wide {
+            grapheme.width_cjk()
+        } else {
+            grapheme.width()
+        };
+        // a cluster is drawn in one or two cells, no matter how many characters it combines
+        width.min(2)
+    }
+}
+
+fn is_private_use(c: char) -> bool {
+    matches!(c, '\u{E000}'..='\u{F8FF}' | '\u{F0000}'..='\u{FFFFD}' | '\u{100000}'..='\u{10FFFD}')
+}
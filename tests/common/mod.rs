@@ -0,0 +1,75 @@
+// Shared by the integration tests, which each use only some of it.
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use unprompt::{Context, Prompt, parse_config_str};
+
+// no padding, so a rendered line is its sections with '.' filling the rest
+const PROMPT: &str = r#"
+[prompt]
+section_pad = 0
+surround_pad = 0
+section_fill = ""
+blank_fill = "."
+shell = "bash"
+"#;
+
+// a context with only the terminal width and the given variables
+pub fn context(columns: usize, env: &[(&str, &str)]) -> Context {
+    Context {
+        columns,
+        env: env
+            .iter()
+            .map(|(var, value)| (var.to_string(), value.to_string()))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+// 2023-11-14 22:13:20 UTC
+pub fn time() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+}
+
+pub fn config(sections: &str) -> Prompt<'static> {
+    parse_config_str(&format!("{PROMPT}{sections}"))
+}
+
+// escapes are written as \e and bash's markers as \[ \], so expected output stays readable
+pub fn visible(text: &str) -> String {
+    text.replace('\x1B', "\\e")
+        .replace('\x01', "\\[")
+        .replace('\x02', "\\]")
+}
+
+// a temporary directory of its own for each test, with a project inside it to use as the
+// current directory, removed when the test ends
+pub struct Sandbox(PathBuf);
+
+pub fn sandbox(prefix: &str, test: &str) -> Sandbox {
+    let root = env::temp_dir().join(format!("unprompt-{prefix}-{}-{test}", process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("project")).unwrap();
+    Sandbox(root)
+}
+
+impl Deref for Sandbox {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
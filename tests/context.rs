@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+
+use unprompt::Context;
+
+mod common;
+
+const SECTIONS: &str = r#"
+[sections.pwd]
+type = "path"
+format = "%p "
+options = ["~"]
+
+[sections.venv]
+path = "$VIRTUAL_ENV"
+format = "(%p) "
+order = 1
+options = ["not_empty"]
+
+[sections.time]
+type = "time"
+time_format = "%S"
+format = "%t"
+order = 2
+"#;
+
+fn render(context: &Context) -> String {
+    common::config(SECTIONS).render(context).prompt
+}
+
+#[test]
+fn renders_from_the_context_alone() {
+    let context = Context {
+        cwd: PathBuf::from("/home/user/src"),
+        time: common::time(),
+        ..common::context(20, &[("HOME", "/home/user"), ("VIRTUAL_ENV", "/opt/venv")])
+    };
+    assert_eq!(render(&context), "~/src (/opt/venv) 20");
+}
+
+#[test]
+fn unset_path_variables_are_empty() {
+    let context = Context {
+        cwd: PathBuf::from("/srv"),
+        time: common::time(),
+        ..common::context(20, &[])
+    };
+    assert_eq!(render(&context), "/srv 20");
+}
+
+#[test]
+fn render_times_every_section() {
+    let rendered = common::config(SECTIONS).render(&Context::default());
+    let collected: Vec<(&str, &str)> = rendered
+        .timings
+        .collect
+        .iter()
+        .map(|(section, segment, _)| (section.as_str(), segment.as_str()))
+        .collect();
+    assert_eq!(
+        collected,
+        [("pwd", "path"), ("venv", "env"), ("time", "time")]
+    );
+}
+
+#[test]
+fn interpolates_variables_like_a_shell() {
+    let context = Context {
+        hostname: "host".to_string(),
+        ..common::context(80, &[("USER", "user"), ("EMPTY", "")])
+    };
+    for (value, expected) in [
+        ("$USER@$HOST", "user@host"),
+        ("${USER}:prod", "user:prod"),
+        ("$UNSET.", "."),
+        ("${EMPTY:-default}", "default"),
+        ("${EMPTY-default}", ""),
+        ("${UNSET:-${USER}s}", "users"),
+        (r"\$USER costs $5 $", "$USER costs $5 $"),
+        (r"a\b\\", r"a\b\"),
+    ] {
+        assert_eq!(context.interpolate(value), expected, "{value}");
+    }
+}
+
+#[test]
+fn env_sections_interpolate_text_path_and_icon() {
+    let config = common::config(
+        r#"
+[sections.login]
+text = "${USER}@$HOST"
+path = "~/$PROJECT"
+icon = "${ICON:->}"
+format = "%i%t:%p"
+"#,
+    );
+    let context = Context {
+        hostname: "host".to_string(),
+        ..common::context(30, &[("USER", "user"), ("PROJECT", "unprompt")])
+    };
+    assert_eq!(config.render(&context).prompt, ">user@host:~/unprompt");
+}
+
+#[test]
+fn every_segment_interpolates_its_configured_icon() {
+    let config = common::config(
+        r#"
+[sections.time]
+type = "time"
+time_format = "%S"
+icon = "${CLOCK:-t}"
+format = "%i%t"
+"#,
+    );
+    let context = Context {
+        time: common::time(),
+        ..common::context(20, &[])
+    };
+    assert_eq!(config.render(&context).prompt, "t20");
+}
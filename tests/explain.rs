@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+
+use unprompt::Context;
+
+mod common;
+
+const SECTIONS: &str = r#"
+[sections.pwd]
+type = "path"
+format = "%p"
+priority = 40
+
+[sections.status]
+text = "$RETURN_CODE"
+format = "%t"
+options = ["not_zero"]
+
+[sections.venv]
+text = "$VIRTUAL_ENV_PROMPT"
+format = "%t"
+options = ["not_empty"]
+
+[sections.off]
+text = "off"
+format = "%t"
+visible = false
+
+[sections.blank]
+text = "blank"
+format = ""
+
+[sections.host]
+text = "a-long-hostname"
+format = "%t"
+priority = 10
+position = "right"
+
+[sections.typo]
+type = "gti"
+"#;
+
+fn reasons(columns: usize) -> Vec<(String, bool, String)> {
+    let context = Context {
+        cwd: PathBuf::from("/srv/projects/app"),
+        ..common::context(columns, &[("RETURN_CODE", "0")])
+    };
+    common::config(SECTIONS)
+        .explain(&context)
+        .into_iter()
+        .map(|explanation| (explanation.name, explanation.shown, explanation.reason))
+        .collect()
+}
+
+#[test]
+fn explains_why_sections_are_hidden() {
+    let reasons = reasons(80);
+    let expected = [
+        ("pwd", true, "shown"),
+        ("status", false, "hidden by not_zero, the text is 0"),
+        (
+            "venv",
+            false,
+            "hidden by not_empty, the text and path are empty",
+        ),
+        ("off", false, "hidden by visible = false"),
+        ("blank", false, "hidden, the format is empty"),
+        ("host", true, "shown"),
+        ("typo", false, "hidden, unknown type 'gti'"),
+    ];
+    assert_eq!(reasons.len(), expected.len());
+    for ((name, shown, reason), expected) in reasons.iter().zip(expected) {
+        assert_eq!((name.as_str(), *shown, reason.as_str()), expected);
+    }
+}
+
+#[test]
+fn explains_what_fit_prompt_did() {
+    let reasons = reasons(15);
+    assert_eq!(
+        reasons[0],
+        (
+            "pwd".to_string(),
+            true,
+            "shortened by fit_prompt to fit in 15 columns".to_string()
+        )
+    );
+    assert_eq!(
+        reasons[5],
+        (
+            "host".to_string(),
+            false,
+            "hidden by fit_prompt, the prompt doesn't fit in 15 columns".to_string()
+        )
+    );
+}
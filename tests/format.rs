@@ -0,0 +1,133 @@
+mod common;
+
+fn render(format: &str, text: &str, columns: usize) -> String {
+    let sections = format!(
+        r#"
+[sections.left]
+text = "{text}"
+format = "{format}"
+
+[sections.right]
+text = "end"
+format = "%t"
+position = "right"
+"#
+    );
+    common::visible(
+        &common::config(&sections)
+            .render(&common::context(columns, &[]))
+            .prompt,
+    )
+}
+
+#[test]
+fn conditional_groups_draw_when_set() {
+    assert_eq!(render("env%?{t}{ (%t)}", "venv", 13), "env (venv)end");
+    assert_eq!(render("env%?{t}{ (%t)}", "", 13), "env.......end");
+}
+
+#[test]
+fn conditional_groups_draw_the_else_branch() {
+    assert_eq!(render("%?{t}{yes}{no}", "x", 8), "yes..end");
+    assert_eq!(render("%?{t}{yes}{no}", "", 8), "no...end");
+}
+
+#[test]
+fn conditional_groups_hold_colors() {
+    assert_eq!(
+        render("%?{t}{%f{red}%t%r}{-}", "on", 6),
+        "\\[\\e[31m\\]on\\[\\e[0m\\].end"
+    );
+}
+
+#[test]
+fn placeholders_are_cut_to_their_max() {
+    assert_eq!(render("%t{max=8}", "feature/login", 11), "feature…end");
+    assert_eq!(
+        render("%t{max=8,ellipsis=..}", "feature/login", 11),
+        "featur..end"
+    );
+    assert_eq!(render("%t{max=8}", "main", 11), "main....end");
+}
+
+#[test]
+fn placeholders_are_padded_to_their_min() {
+    assert_eq!(render("[%-6t]", "main", 12), "[main  ].end");
+    assert_eq!(render("[%6t]", "main", 12), "[  main].end");
+    assert_eq!(render("[%t{min=6}]", "main", 12), "[main  ].end");
+}
+
+#[test]
+fn fit_prompt_cuts_placeholders_before_hiding() {
+    assert_eq!(
+        render("%t{max=20}", "feature/very-long-branch", 12),
+        "feature/…end"
+    );
+}
+
+#[test]
+fn compact_formats_cut_their_own_placeholders() {
+    let rendered = common::config(
+        r#"
+[sections.left]
+text = "feature/very-long-branch-name"
+icon = "branch"
+format = "%i %t{max=30}"
+short_format = "%t{max=30}"
+
+[sections.right]
+text = "twenty-columns-wide."
+format = "%t"
+position = "right"
+priority = 100
+"#,
+    )
+    .render(&common::context(25, &[]));
+    assert_eq!(rendered.prompt, "feat…twenty-columns-wide.");
+}
+
+#[test]
+fn format_mistakes_are_returned_instead_of_printed() {
+    let rendered = common::config(
+        r#"
+[sections.left]
+format = "a%xb%e"
+"#,
+    )
+    .render(&common::context(10, &[]));
+    assert_eq!(rendered.prompt, "ab");
+    assert_eq!(
+        rendered.errors,
+        [
+            "section 'left': unknown placeholder '%x'",
+            "section 'left': %e needs an escape code argument"
+        ]
+    );
+}
+
+#[test]
+fn unknown_types_and_keys_hide_the_section() {
+    let rendered = common::config(
+        r#"
+[sections.typo]
+type = "gti"
+
+[sections.extra]
+text = "shown"
+colour = "red"
+
+[sections.fine]
+text = "fine"
+format = "%t"
+"#,
+    )
+    .render(&common::context(10, &[]));
+    assert_eq!(rendered.prompt, "fine");
+    assert_eq!(
+        rendered.errors,
+        [
+            "section 'typo': unknown type 'gti'",
+            "section 'extra': unknown key 'colour'"
+        ]
+    );
+}
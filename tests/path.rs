@@ -0,0 +1,167 @@
+// Renders paths from a temporary tree, since shortening, project roots, and the path icons
+// all look at the filesystem. HOME is the tree's root, so paths render from '~'.
+
+use std::path::Path;
+
+use unprompt::Context;
+
+mod common;
+
+// ~/programs           shares "pro" with project
+// ~/project/.git       marks the project root
+// ~/project/src/app    the current directory unless a test picks another
+fn tree(test: &str) -> common::Sandbox {
+    let root = common::sandbox("path", test);
+    for dir in ["programs", "project/.git", "project/src/app"] {
+        std::fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    root
+}
+
+fn render(root: &Path, cwd: &Path, columns: usize, options: &str) -> String {
+    let config = common::config(&format!(
+        r#"
+[sections.pwd]
+type = "path"
+{options}
+"#
+    ));
+    let context = Context {
+        cwd: cwd.to_path_buf(),
+        ..common::context(columns, &[("HOME", root.to_str().unwrap())])
+    };
+    common::visible(&config.render(&context).prompt)
+}
+
+#[test]
+fn unique_shortening_keeps_prefixes_siblings_dont_share() {
+    let root = tree("unique");
+    let cwd = root.join("project/src/app");
+    let options = "format = \"%p\"\noptions = [\"~\"]";
+    assert_eq!(render(&root, &cwd, 12, options), "~/p/src/app");
+    let options = format!("{options}\nshorten = \"unique\"");
+    assert_eq!(render(&root, &cwd, 12, &options), "~/proj/s/app");
+    assert_eq!(render(&root, &cwd, 10, &options), "~/proj/s/a");
+}
+
+#[test]
+fn repo_paths_start_at_the_project_root() {
+    let root = tree("repo");
+    let options = "format = \"%?{R}{%R%p}{outside}\"\noptions = [\"repo\"]";
+    assert_eq!(
+        render(&root, &root.join("project/src/app"), 80, options),
+        "project/src/app"
+    );
+    assert_eq!(
+        render(&root, &root.join("programs"), 80, options),
+        "outside"
+    );
+    std::fs::write(root.join("project/src/Cargo.toml"), "").unwrap();
+    let options = format!("{options}\nroot_markers = [\"Cargo.toml\"]");
+    assert_eq!(
+        render(&root, &root.join("project/src/app"), 80, &options),
+        "src/app"
+    );
+}
+
+#[test]
+fn substitutions_apply_in_order_before_home() {
+    let root = tree("substitutions");
+    let cwd = root.join("project/src/app");
+    let project = root.join("project");
+    let options = format!(
+        r#"format = "%p"
+options = ["~"]
+substitutions = [
+  {{ prefix = "{}", replace = "P" }},
+  {{ regex = "^P/src/(.*)$", replace = "src:$1" }},
+]"#,
+        project.display()
+    );
+    assert_eq!(render(&root, &cwd, 80, &options), "src:app");
+    // '~' isn't there yet when substitutions run
+    let options = r#"format = "%p"
+options = ["~"]
+substitutions = [{ regex = "^~", replace = "home" }]"#;
+    assert_eq!(render(&root, &cwd, 80, options), "~/project/src/app");
+}
+
+#[test]
+fn parts_have_their_own_formats() {
+    let root = tree("parts");
+    let options = r#"format = "%p"
+options = ["~"]
+parent_format = "(%p)"
+separator_format = "|"
+basename_format = "[%p]""#;
+    assert_eq!(
+        render(&root, &root.join("project/src/app"), 80, options),
+        "(~)|(project)|(src)|[app]"
+    );
+}
+
+#[test]
+fn icons_show_what_the_directory_is() {
+    let root = tree("icons");
+    let options = r#"format = "%l%m%s%S"
+readonly_icon = "RO"
+missing_icon = "X"
+symlink_icon = "@ ""#;
+    assert_eq!(
+        render(&root, &root.join("project/src/app"), 120, options),
+        ""
+    );
+    assert_eq!(render(&root, &root.join("project/gone"), 120, options), "X");
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(root.join("project/src"), root.join("link")).unwrap();
+        let target = root.join("project/src/app").canonicalize().unwrap();
+        assert_eq!(
+            render(&root, &root.join("link/app"), 120, options),
+            format!("@ {}", target.display())
+        );
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn read_only_directories_are_marked() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // root can write anywhere
+    if unsafe { libc::geteuid() } == 0 {
+        return;
+    }
+    let root = tree("readonly");
+    let locked = root.join("project/locked");
+    std::fs::create_dir(&locked).unwrap();
+    std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o555)).unwrap();
+    let options = "format = \"%l\"\nreadonly_icon = \"RO\"";
+    assert_eq!(render(&root, &locked, 80, options), "RO");
+}
+
+#[test]
+fn dots_and_trailing_slashes_are_not_symlinks() {
+    let root = tree("normalize");
+    let cwd = root.join("project/src/../src/app/");
+    let options = "format = \"%s%S%p\"\nsymlink_icon = \"@\"\noptions = [\"~\"]";
+    assert_eq!(render(&root, &cwd, 80, options), "~/project/src/app");
+    let options = "format = \"%R:%p\"\noptions = [\"repo\"]";
+    assert_eq!(render(&root, &cwd, 80, options), "project:/src/app");
+}
+
+#[test]
+fn short_formats_can_keep_the_full_path() {
+    let root = tree("short");
+    let options = format!(
+        r#"format = "%i %p"
+short_format = "%p"
+icon = "{}"
+options = ["~"]"#,
+        "i".repeat(45)
+    );
+    assert_eq!(
+        render(&root, &root.join("project/src/app"), 50, &options),
+        "~/project/src/app"
+    );
+}
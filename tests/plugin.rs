@@ -0,0 +1,122 @@
+// Runs plugins that are shell scripts, so these only run where there's a /bin/sh.
+#![cfg(unix)]
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use unprompt::Context;
+
+mod common;
+
+fn write_script(root: &Path, script: &str) -> PathBuf {
+    let path = root.join("unprompt-plugin-test");
+    fs::write(&path, format!("#!/bin/sh\n{script}")).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+fn render(root: &Path, command: &Path, options: &str, env: &[(&str, &str)]) -> String {
+    let config = common::config(&format!(
+        r#"
+[sections.plugin]
+type = "plugin"
+command = "{}"
+{options}
+"#,
+        command.display()
+    ));
+    let context = Context {
+        cwd: root.join("project"),
+        status: Some(1),
+        ..common::context(40, env)
+    };
+    common::visible(&config.render(&context).prompt)
+}
+
+#[test]
+fn the_example_plugin_answers() {
+    let example = Path::new("configs/plugins/unprompt-plugin-example")
+        .canonicalize()
+        .unwrap();
+    let root = common::sandbox("plugin", "example");
+    assert_eq!(
+        render(
+            &root,
+            &example,
+            "env = [\"USER\"]\nformat = \"%f%t%r\"",
+            &[("USER", "ann")]
+        ),
+        "\\[\\e[31m\\]hello ann\\[\\e[0m\\]"
+    );
+}
+
+#[test]
+fn plugins_run_in_the_current_directory() {
+    let root = common::sandbox("plugin", "cwd");
+    let script = write_script(&root, r#"printf '{"text":"%s"}' "$(basename "$(pwd)")""#);
+    assert_eq!(render(&root, &script, r#"format = "%t""#, &[]), "project");
+}
+
+#[test]
+fn plugins_only_see_listed_variables() {
+    let root = common::sandbox("plugin", "env");
+    let script = write_script(
+        &root,
+        r#"printf '{"text":"%s %s %s"}' "$LISTED" "${UNLISTED:-unset}" "${HOME:-unset}""#,
+    );
+    let env = [("LISTED", "listed"), ("UNLISTED", "unlisted")];
+    assert_eq!(
+        render(&root, &script, "env = [\"LISTED\"]\nformat = \"%t\"", &env),
+        "listed unset unset"
+    );
+}
+
+#[cfg(target_os = "linux")]
+// a zombie has exited, it's only waiting for its parent
+fn is_running(pid: &str) -> bool {
+    fs::read_to_string(format!("/proc/{}/stat", pid.trim())).is_ok_and(|stat| {
+        !stat
+            .rsplit(')')
+            .next()
+            .unwrap()
+            .trim_start()
+            .starts_with('Z')
+    })
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn plugins_are_stopped_at_the_timeout() {
+    let root = common::sandbox("plugin", "timeout");
+    let script = write_script(
+        &root,
+        r#"sleep 3 &
+echo $! > ../sleep.pid
+echo $$ > ../plugin.pid
+wait
+printf '{"text":"late"}'"#,
+    );
+    let started = Instant::now();
+    assert_eq!(
+        render(&root, &script, "timeout = 200\nformat = \"%t\"", &[]),
+        ""
+    );
+    assert!(started.elapsed() < Duration::from_secs(2));
+    for pid_file in ["plugin.pid", "sleep.pid"] {
+        let pid = fs::read_to_string(root.join(pid_file)).unwrap();
+        // killed processes can take a moment to go away
+        let killed = Instant::now();
+        while is_running(&pid) {
+            assert!(
+                killed.elapsed() < Duration::from_secs(1),
+                "{pid_file} is still running"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,114 @@
+// Renders configs at fixed widths against a fake context and compares them with the
+// golden files in tests/snapshots. Run with UPDATE_SNAPSHOTS=1 to rewrite the golden files
+// after an intended change.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use unprompt::{Context, parse_config};
+
+mod common;
+
+const COLUMNS: [usize; 4] = [20, 40, 80, 120];
+
+fn fake_context(columns: usize) -> Context {
+    let env = [
+        ("HOME", "/home/user"),
+        ("PWD", "/home/user/projects/unprompt/src"),
+        ("USER", "user"),
+        ("RETURN_CODE", "1"),
+        ("VIRTUAL_ENV_PROMPT", "venv"),
+    ];
+    Context {
+        cwd: PathBuf::from("/home/user/projects/unprompt/src"),
+        status: Some(1),
+        time: common::time(),
+        hostname: "host".to_string(),
+        ..common::context(columns, &env)
+    }
+}
+
+// escapes are written as \e and newlines as \n, so each output stays on one line
+fn visible(text: &str) -> String {
+    common::visible(text).replace('\n', "\\n")
+}
+
+fn render_snapshot(config_path: &Path) -> String {
+    let prompt = parse_config(config_path.to_str());
+    let mut snapshot = String::new();
+    for columns in COLUMNS {
+        let rendered = prompt.render(&fake_context(columns));
+        snapshot += &format!("# columns = {columns}\n");
+        let outputs = [
+            ("prompt", &rendered.prompt),
+            ("rprompt", &rendered.rprompt),
+            ("transient", &rendered.transient),
+            ("continuation", &rendered.continuation),
+            ("select", &rendered.select),
+            ("trace", &rendered.trace),
+        ];
+        for (name, output) in outputs {
+            snapshot += &format!("{name}: {}\n", visible(output));
+        }
+    }
+    snapshot
+}
+
+fn check_snapshots(config_paths: &[&Path]) {
+    let update = env::var("UPDATE_SNAPSHOTS").is_ok();
+    let mut mismatches = Vec::new();
+    for config_path in config_paths {
+        // configs/zsh/default.toml is stored as configs_zsh_default.snap
+        let name = config_path
+            .with_extension("")
+            .to_str()
+            .unwrap()
+            .replace('/', "_");
+        let snapshot_path = Path::new("tests/snapshots").join(format!("{name}.snap"));
+        let snapshot = render_snapshot(config_path);
+        if update {
+            fs::write(&snapshot_path, &snapshot).unwrap();
+        } else if fs::read_to_string(&snapshot_path).ok().as_ref() != Some(&snapshot) {
+            eprintln!(
+                "{} doesn't match, rendered:\n{snapshot}",
+                snapshot_path.display()
+            );
+            mismatches.push(snapshot_path);
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "snapshots don't match: {mismatches:?}, run with UPDATE_SNAPSHOTS=1 if the change is intended"
+    );
+}
+
+fn toml_files(dir: &str) -> Vec<PathBuf> {
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "toml")
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn bundled_configs() {
+    let mut config_paths = vec![Path::new("default.toml").to_path_buf()];
+    config_paths.extend(toml_files("configs/bash"));
+    config_paths.extend(toml_files("configs/zsh"));
+    let config_paths: Vec<&Path> = config_paths.iter().map(|path| path.as_path()).collect();
+    check_snapshots(&config_paths);
+}
+
+#[test]
+fn fixtures() {
+    let config_paths = toml_files("tests/fixtures");
+    let config_paths: Vec<&Path> = config_paths.iter().map(|path| path.as_path()).collect();
+    check_snapshots(&config_paths);
+}
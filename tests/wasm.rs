@@ -0,0 +1,125 @@
+// Runs tiny WebAssembly plugins against the host, to check what the sandbox lets through.
+#![cfg(feature = "wasm")]
+
+use std::{fs, path::Path};
+
+use unprompt::Context;
+
+mod common;
+
+// calls one host import with a string argument and answers with what it returned, or
+// hides the section when the host refused
+fn host_call_plugin(import: &str, argument: &str) -> String {
+    format!(
+        r#"
+(module
+  (import "unprompt" "{import}" (func $host (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "{argument}")
+  (func (export "alloc") (param i32) (result i32) (i32.const 4096))
+  (func (export "collect") (param i32 i32) (result i64)
+    (local $n i32)
+    (local.set $n (call $host (i32.const 0) (i32.const {len}) (i32.const 1024) (i32.const 1024)))
+    (if (i32.lt_s (local.get $n) (i32.const 0)) (then (return (i64.const 0))))
+    (i64.or (i64.shl (i64.const 1024) (i64.const 32)) (i64.extend_i32_u (local.get $n)))))
+"#,
+        len = argument.len()
+    )
+}
+
+// the project holds a file the plugin may read, and next to it is one it may not
+fn sandbox(test: &str) -> common::Sandbox {
+    let root = common::sandbox("wasm", test);
+    fs::write(root.join("outside.json"), r#"{"text":"outside"}"#).unwrap();
+    fs::write(root.join("project/inside.json"), r#"{"text":"inside"}"#).unwrap();
+    root
+}
+
+fn render(root: &Path, plugin: &str, env: &[(&str, &str)]) -> String {
+    let wasm_path = root.join("plugin.wasm");
+    fs::write(&wasm_path, wat::parse_str(plugin).unwrap()).unwrap();
+    let config = common::config(&format!(
+        r#"
+[sections.plugin]
+type = "wasm"
+wasm = "{}"
+env = ["LISTED"]
+format = "%t"
+"#,
+        wasm_path.display()
+    ));
+    let context = Context {
+        cwd: root.join("project"),
+        ..common::context(40, env)
+    };
+    config.render(&context).prompt
+}
+
+#[test]
+fn read_file_reads_inside_the_current_directory() {
+    let root = sandbox("inside");
+    let plugin = host_call_plugin("read_file", "inside.json");
+    assert_eq!(render(&root, &plugin, &[]), "inside");
+}
+
+#[test]
+fn read_file_refuses_paths_outside_the_current_directory() {
+    let root = sandbox("outside");
+    let absolute = root.join("outside.json");
+    for path in ["../outside.json", absolute.to_str().unwrap()] {
+        let plugin = host_call_plugin("read_file", path);
+        assert_eq!(render(&root, &plugin, &[]), "", "{path}");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn read_file_refuses_symlinks_out_of_the_current_directory() {
+    let root = sandbox("symlink");
+    std::os::unix::fs::symlink(root.join("outside.json"), root.join("project/link.json")).unwrap();
+    let plugin = host_call_plugin("read_file", "link.json");
+    assert_eq!(render(&root, &plugin, &[]), "");
+}
+
+#[test]
+fn env_get_only_sees_listed_variables() {
+    let root = sandbox("env");
+    let env = [
+        ("LISTED", r#"{"text":"listed"}"#),
+        ("UNLISTED", r#"{"text":"unlisted"}"#),
+    ];
+    let plugin = host_call_plugin("env_get", "LISTED");
+    assert_eq!(render(&root, &plugin, &env), "listed");
+    let plugin = host_call_plugin("env_get", "UNLISTED");
+    assert_eq!(render(&root, &plugin, &env), "");
+}
+
+#[test]
+fn responses_past_the_end_of_memory_are_refused() {
+    let root = sandbox("response");
+    let plugin = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param i32) (result i32) (i32.const 4096))
+  (func (export "collect") (param i32 i32) (result i64) (i64.const 0xFFFFFFFF)))
+"#;
+    assert_eq!(render(&root, plugin, &[]), "");
+}
+
+#[test]
+fn memory_growth_is_limited() {
+    let root = sandbox("grow");
+    // 1024 pages are 64 MiB
+    let plugin = r#"
+(module
+  (memory (export "memory") 1)
+  (data (i32.const 0) "{\"text\":\"refused\"}")
+  (data (i32.const 32) "{\"text\":\"grown\"}")
+  (func (export "alloc") (param i32) (result i32) (i32.const 4096))
+  (func (export "collect") (param i32 i32) (result i64)
+    (if (i32.eq (memory.grow (i32.const 1024)) (i32.const -1))
+      (then (return (i64.const 18))))
+    (i64.or (i64.shl (i64.const 32) (i64.const 32)) (i64.const 16))))
+"#;
+    assert_eq!(render(&root, plugin, &[]), "refused");
+}
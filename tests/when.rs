@@ -0,0 +1,90 @@
+use unprompt::Context;
+
+mod common;
+
+const SECTIONS: &str = r#"
+[sections.user]
+text = "$USER"
+format = "%f%t%r"
+foreground = "cyan"
+
+[[sections.user.when]]
+condition = 'user == "root"'
+foreground = "red"
+
+[[sections.user.when]]
+condition = "env.SSH_CONNECTION exists"
+format = "%f%t@remote%r"
+
+[sections.prompt]
+format = "%f%i%r "
+icon = "$"
+foreground = "green"
+position = "prompt"
+
+[[sections.prompt.when]]
+condition = "status != 0"
+foreground = "red"
+
+[[sections.prompt.when]]
+condition = "status >= 128 and status < 256"
+icon = "!"
+"#;
+
+fn render(env: &[(&str, &str)], status: Option<i32>) -> String {
+    let context = Context {
+        status,
+        ..common::context(20, env)
+    };
+    common::visible(&common::config(SECTIONS).render(&context).prompt)
+}
+
+#[test]
+fn no_rules_match() {
+    assert_eq!(
+        render(&[("USER", "ann")], Some(0)),
+        format!(
+            "\\[\\e[36m\\]ann\\[\\e[0m\\]{}\\[\\e[32m\\]$\\[\\e[0m\\] ",
+            ".".repeat(17)
+        )
+    );
+}
+
+#[test]
+fn rules_match_status_user_and_env() {
+    assert_eq!(
+        render(&[("USER", "root"), ("SSH_CONNECTION", "10.0.0.1")], Some(1)),
+        format!(
+            "\\[\\e[31m\\]root@remote\\[\\e[0m\\]{}\\[\\e[31m\\]$\\[\\e[0m\\] ",
+            ".".repeat(9)
+        )
+    );
+}
+
+#[test]
+fn later_rules_add_to_earlier_ones() {
+    assert_eq!(
+        render(&[("USER", "ann")], Some(130)),
+        format!(
+            "\\[\\e[36m\\]ann\\[\\e[0m\\]{}\\[\\e[31m\\]!\\[\\e[0m\\] ",
+            ".".repeat(17)
+        )
+    );
+}
+
+#[test]
+fn an_unknown_status_never_matches() {
+    assert_eq!(
+        render(&[("USER", "ann")], None),
+        format!(
+            "\\[\\e[36m\\]ann\\[\\e[0m\\]{}\\[\\e[32m\\]$\\[\\e[0m\\] ",
+            ".".repeat(17)
+        )
+    );
+}
+
+#[test]
+#[should_panic(expected = "unknown operator")]
+fn invalid_conditions_panic() {
+    common::config(&SECTIONS.replace("status != 0", "status ~ 0"));
+}